@@ -1,192 +1,294 @@
 extern crate proc_macro;
-extern crate syn;
-#[macro_use]
-extern crate quote;
 
-use proc_macro::{TokenStream};
-use syn::Meta;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
 
 /*
 This macro allows a programmer to create a more complicated policied struct.
 It creates a POLICIEDTYPE struct with
+* the wrapper struct itself, holding the inner TYPE and its policy, with
+  serde Serialize/Deserialize and Clone implementations
+* implements Policied<TYPE> trait, and HasPolicied for TYPE
 * make_decomposed constructor that takes in policied structs for protected fields
-* policied getters for all protected fields
-* implements Policied<TYPE> trait
+* make_decomposed_unpolicied constructor that takes in the raw fields
+* policied getters for all protected fields, borrowing getters (x_ref) returning a
+  GPolicied<&OTHER1TYPE> without cloning the field, and setters (set_x) taking a
+  policied value whose policy is merged into the wrapper's
 
-Usage: 
+Usage:
 #[derive(Serialize, Deserialize, Clone, Policied)]
 #[policied(PoliciedTYPE)]
 pub struct TYPE {
-    #[policy_protected(PoliciedOTHER1TYPE)] 
-    pub x: OTHER1TYPE, 
+    #[policy_protected(PoliciedOTHER1TYPE)]
+    pub x: OTHER1TYPE,
 
-    #[policy_protected(PoliciedOTHER2TYPE)] 
-    pub y: OTHER2TYPE, 
+    #[policy_protected(PoliciedOTHER2TYPE)]
+    pub y: OTHER2TYPE,
 
-    pub z: OTHER3TYPE, 
+    pub z: OTHER3TYPE,
 }
 
-Note: PoliciedOTHER1TYPE and PoliciedOTHER2TYPE must have been previously derived.
+By default the policies of all protected fields are merged into a single policy
+for the whole struct, so every getter returns a value carrying all of them. With
+#[policied(PoliciedTYPE, per_field)] each protected field keeps its own policy
+(merged with the policy passed to the constructor) and its getter returns only
+that one; exporting the whole struct still checks all of them.
+
+A bare #[policy_protected] picks the policied type through HasPolicied, so fields
+whose type is itself a derived policied struct (or String, i64, Vec<T>, ...) need no
+wrapper name, and getters can be chained:
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedCourse)]
+pub struct Course {
+    #[policy_protected]
+    pub roster: Roster,         // course.roster() is a PoliciedRoster
+}
+... course.roster().students().get(3).unwrap().grade()
+
+Tuple structs are supported as well; their getters and constructor arguments are
+named after the field index (`_0`, `_1`, ...), with borrowing getters `ref_0` and
+setters `set_0`. Generic parameters are carried over
+to the policied type, e.g. `Record<T>` gets a `PoliciedRecord<T>`.
+
+Enums get a policied type with, for every variant,
+* a make_VARIANT constructor taking policied values for protected fields
+* a VARIANT getter per protected field returning Some(policied field) if the
+  value is that variant and None otherwise; the getter is named VARIANT_FIELD
+  (or VARIANT_INDEX for tuple variants) when the variant has several fields.
+  A borrowing VARIANT_ref getter is generated alongside it.
+Note that these getters reveal which variant is stored, like get_option does for
+policied options.
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedSubmission)]
+pub enum Submission {
+    Text(#[policy_protected(PoliciedString)] String),
+    File(#[policy_protected(GPolicied<Vec<u8>>)] Vec<u8>),
+}
+
+Note: PoliciedOTHER1TYPE and PoliciedOTHER2TYPE must have been previously derived,
+and the crate using the derive must depend on serde.
+Malformed input is reported as a compile error pointing at the offending item.
 */
-// TODO: better error messages!
 #[proc_macro_derive(Policied, attributes(policied, policy_protected))]
 pub fn policied_derive(input: TokenStream) -> TokenStream {
-
   let input = syn::parse_macro_input!(input as syn::DeriveInput);
+  expand_policied(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
 
-  // Get the name of the type we want to implement the trait for, TYPE.
-  let unpolicied_name = &input.ident;
-  let mut policied_name = unpolicied_name.clone();
+// A field of the struct or variant being derived, with the policied type it is
+// exposed as if it is marked #[policy_protected(...)].
+struct PoliciedField {
+  // How the field is accessed: `x` for named fields, `0` for tuple fields.
+  member: syn::Member,
+  // The name used for getters and constructor arguments: `x`, or `_0`.
+  name: syn::Ident,
+  ty: syn::Type,
+  protected: Option<syn::Type>,
+}
+
+struct PoliciedVariant {
+  ident: syn::Ident,
+  fields: Vec<PoliciedField>,
+}
 
+// The arguments of #[policied(PoliciedTYPE, options...)].
+struct PoliciedAttr {
+  name: syn::Ident,
+  per_field: bool,
+}
+
+fn expand_policied(input: &syn::DeriveInput) -> syn::Result<TokenStream2> {
   // Get the name of the policied type from the macro's argument, PoliciedTYPE.
-  for attr in input.attrs.iter() {
-    match attr.parse_meta().unwrap() {
-      Meta::List(inner_list) => {
-        // Could be a list inside of names, but should just be 1. TODO: check that it's just 1
-        for ty in inner_list.nested.iter() {
-          match ty {
-            syn::NestedMeta::Meta(ty_meta) => {
-              policied_name = ty_meta.clone().name();
-              
-            }
-            _ => panic!("Inner list must be type, not string literal"),
-          }
-        }
-      },
-      _ => panic!("policied attr must designate Policied type")
-    }
-    
-  }
-
-  // Iterate over all fields in the struct and collect them into all_fields
-  // all_fields contains name, type, and whether it is policy-protected
-  let mut all_fields: Vec<(syn::Ident, syn::Type, Option<syn::Ident>, bool)> = vec![];
-  match input.data {
-      // Only process structs
-      syn::Data::Struct(ref data_struct) => {
-          // Check the kind of fields the struct contains
-          match data_struct.fields {
-              // Structs with named fields
-              syn::Fields::Named(ref fields_named) => {
-                  // Iterate over the fields
-                  for field in fields_named.named.iter() {
-                      let field_name = field.clone().ident.unwrap();
-                      let mut is_policy_protected = false;
-                      // Get attributes #[..] on each field
-                      for attr in field.attrs.iter() {
-                          // Parse the attribute
-                          let meta = attr.parse_meta().unwrap();
-                          if meta.name().to_string() == "policy_protected" {
-                            match meta {
-                              syn::Meta::List(inner_list) => {
-                                // Get nested return types #[policy_protected(...)]
-                                for ty in inner_list.nested.iter() {
-                                  match ty {
-                                    syn::NestedMeta::Meta(ty_meta) => {
-                                      is_policy_protected = true;
-                                      all_fields.push((field_name.clone(), field.clone().ty, Some(ty_meta.clone().name()), true));
-                                    }
-                                    _ => panic!("Inner list must be type, not string literal"),
-                                  }
-                                }
-                              }
-                              _ => panic!("Must have return type in inner list"),
-                            }
-                          } 
-                      }
-                      if !is_policy_protected {
-                        all_fields.push((field_name.clone(), field.clone().ty, None, false));
-                      }
-                  }
-              }
-
-              // Struct with unnamed fields
-              _ => (),
-          }
+  let attr = policied_attr(input)?;
+  match &input.data {
+    syn::Data::Struct(data_struct) => {
+      let fields = collect_fields(&data_struct.fields)?;
+      Ok(expand_struct(input, &attr, &fields))
+    },
+    syn::Data::Enum(data_enum) => {
+      if attr.per_field {
+        return Err(syn::Error::new(attr.name.span(), "`per_field` is only supported on structs"));
       }
-
-      // Panic when we don't have a struct
-      _ => panic!("Must be a struct"),
+      let variants = data_enum.variants.iter().map(|v| Ok(PoliciedVariant {
+        ident: v.ident.clone(),
+        fields: collect_fields(&v.fields)?,
+      })).collect::<syn::Result<Vec<_>>>()?;
+      Ok(expand_enum(input, &attr, &variants))
+    },
+    syn::Data::Union(data_union) => Err(syn::Error::new(
+      data_union.union_token.span(), "#[derive(Policied)] is not supported on unions"
+    )),
   }
+}
 
-  /*
-  Create list of arguments for make_decomposed. 
-  If a field is marked as policy_protected but doesn't have a corresponding PoliciedOTHERTYPE
-  argument, will panic.
-  Example: 
-  ```
-  x: PoliciedOTHER1TYPE, y: PoliciedOTHER2TYPE, z: OTHER3TYPE,
-  ```
-  */
-  let make_decomposed_arguments = all_fields.clone().iter().fold(
-    quote!(), |es, (name, ty_original, ty_protected, is_protected)| 
-    if *is_protected {
-      match ty_protected {
-        Some(ty) => {
-          quote! { #es #name: #ty, }
-        },
-        None => panic!("No protected type")
-      }
-    } else {
+/*
+Generate the wrapper struct and its Policied<TYPE> impl, the same way
+derive_policied!(TYPE, PoliciedTYPE) does. In per-field mode the wrapper also
+holds a PoliciedTYPEFieldPolicies struct with one policy per protected field,
+while `policy` is the merge of all of them and guards the whole struct.
+*/
+fn expand_wrapper(input: &syn::DeriveInput, attr: &PoliciedAttr, protected_names: &[&syn::Ident]) -> TokenStream2 {
+  let vis = &input.vis;
+  let unpolicied_name = &input.ident;
+  let policied_name = &attr.name;
+  let params = &input.generics.params;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  let field_policies_name = format_ident!("{}FieldPolicies", policied_name);
+
+  let (field_policies_decl, field_policies_field, make_body, remove_field_policies) = if attr.per_field {
+    (
       quote! {
-        #es #name: #ty_original,
-      }
+        #[doc(hidden)]
+        #[derive(serde::Serialize, serde::Deserialize, Clone)]
+        #vis struct #field_policies_name {
+          #(#protected_names: Box<dyn beaver::policy::Policy>,)*
+        }
+      },
+      quote!(field_policies: #field_policies_name,),
+      quote! {
+        Self {
+          field_policies: #field_policies_name { #(#protected_names: policy.clone(),)* },
+          inner,
+          policy,
+        }
+      },
+      quote! {
+        #(self.field_policies.#protected_names = Box::new(beaver::policy::NonePolicy);)*
+      },
+    )
+  } else {
+    (quote!(), quote!(), quote!(Self { inner, policy }), quote!())
+  };
+
+  quote! {
+    #field_policies_decl
+
+    #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    #vis struct #policied_name <#params> #where_clause {
+      inner: #unpolicied_name #ty_generics,
+      policy: Box<dyn beaver::policy::Policy>,
+      #field_policies_field
     }
-  );
 
-  /* 
-  Generate code to merge policies on protected arguments in make_decomposed.
-  Example: 
-  ```
-  let new_policy = policy.merge(x.get_policy()).unwrap().merge(y.get_policy()).unwrap()
-  ```
-  Note: Expects that merging is legal, will panic if it's not. 
-  */
-  let policies = all_fields.clone().iter().fold(
-    quote!(let new_policy = policy), |es, (name, _, _, is_protected)|
-    if *is_protected {
-      quote! {
-        #es.merge(#name.get_policy()).unwrap()
+    impl #impl_generics beaver::policy::HasPolicied for #unpolicied_name #ty_generics #where_clause {
+      type Policied = #policied_name #ty_generics;
+    }
+
+    impl #impl_generics beaver::policy::Policied<#unpolicied_name #ty_generics> for #policied_name #ty_generics #where_clause {
+      fn make(inner: #unpolicied_name #ty_generics, policy: Box<dyn beaver::policy::Policy>) -> Self {
+        #make_body
+      }
+      fn get_policy(&self) -> &Box<dyn beaver::policy::Policy> {
+        &self.policy
+      }
+      fn remove_policy(&mut self) {
+        self.policy = Box::new(beaver::policy::NonePolicy);
+        #remove_field_policies
+      }
+      fn export_check(self, ctxt: &beaver::filter::Context) -> Result<#unpolicied_name #ty_generics, beaver::policy::PolicyError> {
+        beaver::policy::Policy::check_flow(&*self.policy, ctxt).map(|_| self.inner)
+      }
+      fn export_check_borrow(&self, ctxt: &beaver::filter::Context) -> Result<&#unpolicied_name #ty_generics, beaver::policy::PolicyError> {
+        beaver::policy::Policy::check_flow(&*self.policy, ctxt).map(|_| &self.inner)
+      }
+      fn unsafe_export(self) -> #unpolicied_name #ty_generics {
+        self.inner
       }
-    } else {
-      quote!{#es}
     }
-  );
+  }
+}
 
-  /* 
-  Generate code to construct the inner raw type, exporting policied arguments. 
-  Example: 
-  ```
-  x: x.export(),
-  y: x.export(),
-  z: z,
-  ```
-  */
-  let make_decomposed_constructor_inner = all_fields.clone().iter().fold(
-    quote!(), |es, (name, _, _, is_protected)| 
-    if *is_protected {
+/*
+Create list of constructor arguments, taking policied types for protected fields.
+Example:
+```
+x: PoliciedOTHER1TYPE, y: PoliciedOTHER2TYPE, z: OTHER3TYPE,
+```
+*/
+fn decomposed_arguments(fields: &[PoliciedField]) -> TokenStream2 {
+  let arguments = fields.iter().map(|f| {
+    let name = &f.name;
+    let ty = f.protected.as_ref().unwrap_or(&f.ty);
+    quote! { #name: #ty, }
+  });
+  quote!(#(#arguments)*)
+}
+
+/*
+Generate code to merge policies on protected arguments.
+Example:
+```
+let new_policy = policy;
+let new_policy = new_policy.merge(x.get_policy()).unwrap();
+let new_policy = new_policy.merge(y.get_policy()).unwrap();
+```
+Note: Expects that merging is legal, will panic if it's not.
+*/
+fn merged_policies(fields: &[PoliciedField]) -> TokenStream2 {
+  fields.iter().filter(|f| f.protected.is_some()).fold(
+    quote!(let new_policy = policy;), |es, f| {
+      let name = &f.name;
       quote! {
         #es
-        #name: #name.unsafe_export(),
+        let new_policy = beaver::policy::Policy::merge(
+          &*new_policy, beaver::policy::Policied::get_policy(&#name)
+        ).unwrap();
       }
+    }
+  )
+}
+
+/*
+Generate the field initializers of the inner raw value, exporting policied arguments.
+Example:
+```
+x: x.unsafe_export(),
+y: y.unsafe_export(),
+z: z,
+```
+*/
+fn decomposed_initializers(fields: &[PoliciedField]) -> TokenStream2 {
+  let initializers = fields.iter().map(|f| {
+    let member = &f.member;
+    let name = &f.name;
+    if f.protected.is_some() {
+      quote! { #member: beaver::policy::Policied::unsafe_export(#name), }
     } else {
-      quote! {
-        #es
-        #name,
-      }
+      quote! { #member: #name, }
     }
-  );
+  });
+  quote!(#(#initializers)*)
+}
+
+fn expand_struct(input: &syn::DeriveInput, attr: &PoliciedAttr, all_fields: &[PoliciedField]) -> TokenStream2 {
+  // Get the name of the type we want to implement the trait for, TYPE.
+  let unpolicied_name = &input.ident;
+  let policied_name = &attr.name;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  let protected_fields: Vec<&PoliciedField> = all_fields.iter().filter(|f| f.protected.is_some()).collect();
+  let protected_names: Vec<&syn::Ident> = protected_fields.iter().map(|f| &f.name).collect();
+  let field_policies_name = format_ident!("{}FieldPolicies", policied_name);
 
-  /* 
+  let make_decomposed_arguments = decomposed_arguments(all_fields);
+  let policies = merged_policies(all_fields);
+  let make_decomposed_constructor_inner = decomposed_initializers(all_fields);
+
+  /*
   Generate final make_decomposed function.
-  Example: 
+  Example:
   ```
   pub fn make_decomposed(x: PoliciedOTHER1TYPE, y: PoliciedOTHER2TYPE, z: OTHER3TYPE, policy: Box<dyn Policy>) -> Self {
-    let new_policy = policy.merge(x.get_policy()).unwrap().merge(y.get_policy()).unwrap()
+    let new_policy = ...;
     PoliciedTYPE::make(
       TYPE {
-        x: x.export(),
-        y: x.export(),
+        x: x.unsafe_export(),
+        y: y.unsafe_export(),
         z: z,
       },
       new_policy
@@ -194,56 +296,565 @@ pub fn policied_derive(input: TokenStream) -> TokenStream {
   }
   ```
   */
-  let make_decomposed = quote! {
-    pub fn make_decomposed(#make_decomposed_arguments policy: Box<dyn beaver::policy::Policy>) -> Self {
-      #policies;
-      #policied_name::make(
-        #unpolicied_name {
-          #make_decomposed_constructor_inner
-        },
-        new_policy
-      )
+  let make_decomposed = if attr.per_field {
+    // Each protected field's own policy is its argument's policy merged with `policy`.
+    let field_policies = protected_fields.iter().map(|f| {
+      let name = &f.name;
+      quote! {
+        #name: beaver::policy::Policy::merge(
+          &*policy, beaver::policy::Policied::get_policy(&#name)
+        ).unwrap(),
+      }
+    });
+    quote! {
+      pub fn make_decomposed(#make_decomposed_arguments policy: Box<dyn beaver::policy::Policy>) -> Self {
+        let field_policies = #field_policies_name { #(#field_policies)* };
+        #policies
+        #policied_name {
+          inner: #unpolicied_name {
+            #make_decomposed_constructor_inner
+          },
+          policy: new_policy,
+          field_policies,
+        }
+      }
+    }
+  } else {
+    quote! {
+      pub fn make_decomposed(#make_decomposed_arguments policy: Box<dyn beaver::policy::Policy>) -> Self {
+        #policies
+        <Self as beaver::policy::Policied<#unpolicied_name #ty_generics>>::make(
+          #unpolicied_name {
+            #make_decomposed_constructor_inner
+          },
+          new_policy
+        )
+      }
     }
   };
 
-  /* 
-  Generate getters for protected types.
-  Example: 
+  /*
+  Generate getters and setters for protected types.
+  Example:
   ```
   pub fn x(&self) -> PoliciedOTHER1TYPE {
     PoliciedOTHER1TYPE::make(
-      self.inner.clone().x,
+      self.inner.x.clone(),
       self.policy.clone()
     )
   }
+
+  pub fn x_ref(&self) -> GPolicied<&OTHER1TYPE> {
+    GPolicied::make(&self.inner.x, self.policy.clone())
+  }
+
+  pub fn set_x(&mut self, value: PoliciedOTHER1TYPE) -> Result<(), PolicyError> {
+    self.policy = self.policy.merge(value.get_policy())?;
+    self.inner.x = value.unsafe_export();
+    Ok(())
+  }
   ```
   */
-  let expanded_protected = all_fields.clone().iter().fold(
-    quote!(), |es, (name, _, ty_protected, is_protected)| 
-    if *is_protected {
-      match ty_protected {
-        Some(ty) => {
-          quote! { #es
-            pub fn #name(&self) -> #ty {
-              #ty::make(
-                self.inner.clone().#name,
-                self.policy.clone()
-              )}}
+  let expanded_protected = protected_fields.iter().map(|f| {
+    let name = &f.name;
+    let member = &f.member;
+    let field_ty = &f.ty;
+    let ty = f.protected.as_ref().unwrap();
+    let policy = if attr.per_field {
+      quote!(self.field_policies.#name)
+    } else {
+      quote!(self.policy)
+    };
+    // Tuple fields get ref_0 and set_0 rather than _0_ref and set__0.
+    let (ref_name, set_name) = match member {
+      syn::Member::Named(_) => (format_ident!("{}_ref", name), format_ident!("set_{}", name)),
+      syn::Member::Unnamed(index) => (format_ident!("ref_{}", index.index), format_ident!("set_{}", index.index)),
+    };
+    // In per-field mode the field's own policy absorbs the new value's policy too.
+    let merge_field_policy = if attr.per_field {
+      quote! {
+        self.field_policies.#name = beaver::policy::Policy::merge(
+          &*self.field_policies.#name, beaver::policy::Policied::get_policy(&value)
+        )?;
+      }
+    } else {
+      quote!()
+    };
+    quote! {
+      pub fn #name(&self) -> #ty where #field_ty: Clone {
+        <#ty as beaver::policy::Policied<#field_ty>>::make(
+          self.inner.#member.clone(),
+          #policy.clone()
+        )
+      }
+
+      pub fn #ref_name(&self) -> beaver::generic_policied::GPolicied<&#field_ty> {
+        <beaver::generic_policied::GPolicied<&#field_ty> as beaver::policy::Policied<&#field_ty>>::make(
+          &self.inner.#member,
+          #policy.clone()
+        )
+      }
+
+      pub fn #set_name(&mut self, value: #ty) -> Result<(), beaver::policy::PolicyError> {
+        self.policy = beaver::policy::Policy::merge(
+          &*self.policy, beaver::policy::Policied::get_policy(&value)
+        )?;
+        #merge_field_policy
+        self.inner.#member = <#ty as beaver::policy::Policied<#field_ty>>::unsafe_export(value);
+        Ok(())
+      }
+    }
+  });
+
+  /*
+  Generate the constructor from raw fields, which are all protected by `policy`.
+  Example:
+  ```
+  pub fn make_decomposed_unpolicied(x: OTHER1TYPE, y: OTHER2TYPE, z: OTHER3TYPE, policy: Box<dyn Policy>) -> Self {
+    PoliciedTYPE::make(TYPE { x: x, y: y, z: z, }, policy)
+  }
+  ```
+  */
+  let unpolicied_arguments = all_fields.iter().map(|f| {
+    let name = &f.name;
+    let ty = &f.ty;
+    quote! { #name: #ty, }
+  });
+  let unpolicied_initializers = all_fields.iter().map(|f| {
+    let member = &f.member;
+    let name = &f.name;
+    quote! { #member: #name, }
+  });
+  let make_decomposed_unpolicied = quote! {
+    pub fn make_decomposed_unpolicied(#(#unpolicied_arguments)* policy: Box<dyn beaver::policy::Policy>) -> Self {
+      <Self as beaver::policy::Policied<#unpolicied_name #ty_generics>>::make(
+        #unpolicied_name { #(#unpolicied_initializers)* },
+        policy
+      )
+    }
+  };
+
+  let wrapper = expand_wrapper(input, attr, &protected_names);
+
+  // Putting it all together!
+  quote! {
+    #wrapper
+
+    impl #impl_generics #policied_name #ty_generics #where_clause {
+      #make_decomposed
+      #make_decomposed_unpolicied
+      #(#expanded_protected)*
+    }
+  }
+}
+
+fn expand_enum(input: &syn::DeriveInput, attr: &PoliciedAttr, variants: &[PoliciedVariant]) -> TokenStream2 {
+  let unpolicied_name = &input.ident;
+  let policied_name = &attr.name;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+  let methods = variants.iter().map(|v| {
+    let variant = &v.ident;
+    let snake = snake_case(variant);
+
+    /*
+    Generate a constructor per variant.
+    Example:
+    ```
+    pub fn make_text(_0: PoliciedString, policy: Box<dyn Policy>) -> Self {
+      let new_policy = ...;
+      PoliciedSubmission::make(Submission::Text { 0: _0.unsafe_export(), }, new_policy)
+    }
+    ```
+    */
+    let make_name = format_ident!("make_{}", snake);
+    let arguments = decomposed_arguments(&v.fields);
+    let policies = merged_policies(&v.fields);
+    let initializers = decomposed_initializers(&v.fields);
+    let make = quote! {
+      pub fn #make_name(#arguments policy: Box<dyn beaver::policy::Policy>) -> Self {
+        #policies
+        <Self as beaver::policy::Policied<#unpolicied_name #ty_generics>>::make(
+          #unpolicied_name::#variant { #initializers },
+          new_policy
+        )
+      }
+    };
+
+    /*
+    Generate getters for protected fields of the variant.
+    Example:
+    ```
+    pub fn text(&self) -> Option<PoliciedString> {
+      match &self.inner {
+        Submission::Text { 0: v, .. } => Some(PoliciedString::make(v.clone(), self.policy.clone())),
+        _ => None,
+      }
+    }
+    ```
+    */
+    let single_field = v.fields.len() == 1;
+    let getters = v.fields.iter().filter(|f| f.protected.is_some()).map(|f| {
+      let member = &f.member;
+      let field_ty = &f.ty;
+      let ty = f.protected.as_ref().unwrap();
+      let getter = if single_field {
+        format_ident!("{}", snake)
+      } else {
+        format_ident!("{}_{}", snake, f.name.to_string().trim_start_matches('_'))
+      };
+      let ref_getter = format_ident!("{}_ref", getter);
+      quote! {
+        #[allow(unreachable_patterns)]
+        pub fn #getter(&self) -> Option<#ty> where #field_ty: Clone {
+          match &self.inner {
+            #unpolicied_name::#variant { #member: v, .. } => Some(
+              <#ty as beaver::policy::Policied<#field_ty>>::make(v.clone(), self.policy.clone())
+            ),
+            _ => None,
+          }
+        }
+
+        #[allow(unreachable_patterns)]
+        pub fn #ref_getter(&self) -> Option<beaver::generic_policied::GPolicied<&#field_ty>> {
+          match &self.inner {
+            #unpolicied_name::#variant { #member: v, .. } => Some(
+              <beaver::generic_policied::GPolicied<&#field_ty> as beaver::policy::Policied<&#field_ty>>::make(
+                v, self.policy.clone()
+              )
+            ),
+            _ => None,
+          }
+        }
+      }
+    });
+    quote! {
+      #make
+      #(#getters)*
+    }
+  });
+
+  let wrapper = expand_wrapper(input, attr, &[]);
+
+  quote! {
+    #wrapper
+
+    impl #impl_generics #policied_name #ty_generics #where_clause {
+      #(#methods)*
+    }
+  }
+}
+
+// Reads PoliciedTYPE and its options out of #[policied(PoliciedTYPE, ...)] on the type.
+fn policied_attr(input: &syn::DeriveInput) -> syn::Result<PoliciedAttr> {
+  let mut found: Option<PoliciedAttr> = None;
+  for attr in input.attrs.iter().filter(|a| a.path().is_ident("policied")) {
+    if found.is_some() {
+      return Err(syn::Error::new_spanned(attr, "duplicate #[policied(...)] attribute"));
+    }
+    found = Some(attr.parse_args_with(|stream: syn::parse::ParseStream| {
+      let name: syn::Ident = stream.parse().map_err(|e| syn::Error::new(
+        e.span(), "expected the name of the policied type, e.g. #[policied(PoliciedGrade)]"
+      ))?;
+      let mut per_field = false;
+      while !stream.is_empty() {
+        stream.parse::<syn::Token![,]>()?;
+        if stream.is_empty() {
+          break;
+        }
+        let option: syn::Ident = stream.parse()?;
+        if option == "per_field" {
+          per_field = true;
+        } else {
+          return Err(syn::Error::new(option.span(), "unknown option, expected `per_field`"));
+        }
+      }
+      Ok(PoliciedAttr { name, per_field })
+    })?);
+  }
+  found.ok_or_else(|| syn::Error::new(
+    input.ident.span(),
+    "missing #[policied(PoliciedType)] attribute naming the policied type",
+  ))
+}
+
+// Collects the fields of a struct or variant, along with their protected types.
+fn collect_fields(fields: &syn::Fields) -> syn::Result<Vec<PoliciedField>> {
+  let mut all_fields = vec![];
+  for (index, field) in fields.iter().enumerate() {
+    let mut protected = None;
+    // Get attributes #[..] on each field
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("policy_protected")) {
+      if protected.is_some() {
+        return Err(syn::Error::new_spanned(attr, "duplicate #[policy_protected(...)] attribute"));
+      }
+      // Get the return type #[policy_protected(...)]. A bare #[policy_protected] uses
+      // the policied type registered for the field's type through HasPolicied.
+      let ty: syn::Type = match &attr.meta {
+        syn::Meta::List(list) => syn::parse2(list.tokens.clone()).map_err(|e| syn::Error::new(
+          e.span(), "expected a single policied type, e.g. #[policy_protected(PoliciedString)]"
+        ))?,
+        syn::Meta::Path(_) => {
+          let field_ty = &field.ty;
+          syn::parse_quote!(<#field_ty as beaver::policy::HasPolicied>::Policied)
         },
-        None => panic!("No protected type")
+        syn::Meta::NameValue(_) => return Err(syn::Error::new_spanned(
+          attr, "expected #[policy_protected] or #[policy_protected(PoliciedType)]"
+        )),
+      };
+      protected = Some(ty);
+    }
+    let (member, name) = match &field.ident {
+      Some(ident) => (syn::Member::Named(ident.clone()), ident.clone()),
+      None => (
+        syn::Member::Unnamed(syn::Index { index: index as u32, span: field.ty.span() }),
+        format_ident!("_{}", index),
+      ),
+    };
+    all_fields.push(PoliciedField { member, name, ty: field.ty.clone(), protected });
+  }
+  Ok(all_fields)
+}
+
+// FileUpload -> file_upload
+fn snake_case(ident: &syn::Ident) -> String {
+  let mut snake = String::new();
+  for (i, c) in ident.to_string().chars().enumerate() {
+    if c.is_uppercase() {
+      if i > 0 {
+        snake.push('_');
       }
-      
+      snake.extend(c.to_lowercase());
     } else {
-      quote!(#es)
+      snake.push(c);
+    }
+  }
+  snake
+}
+
+/*
+This macro implements Policy for a struct from declarative attributes that refer
+to the struct's own fields, generating check, the usual merge (wrapping both
+policies in a MergePolicy) and the typetag registration.
+* #[allow_file(name = FIELD)] allows files whose name equals FIELD
+* #[allow_file(path = FIELD)] allows files inside the directory FIELD
+* #[allow_remote_ip(FIELD)] allows network connections to the IP address FIELD
+* every other kind of context is rejected, with the message given by an optional
+  #[deny_other(message = "...")]; #[allow_other] lets them through instead
+Attributes of the same kind are alternatives: a file is allowed if any
+#[allow_file] matches. Listing several fields in one attribute, as in
+#[allow_remote_ip(student_ip, instructor_ip)], adds one rule per field.
+FIELD may be a String, an IpAddr, or an Option or Vec of those (see PolicyValue).
+Each allow attribute takes an optional `message = "..."` used when the flow is
+rejected. Purpose-tagged and composite contexts are unwrapped
+before checking.
+
+Usage:
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(name = student_id, message = "File must belong to same student")]
+#[allow_file(name = instructor_id)]
+#[allow_remote_ip(instructor_ip)]
+#[deny_other(message = "Grades may only go to the student or instructor")]
+pub struct GradePolicy {
+    pub student_id: String,
+    pub instructor_id: String,
+    pub instructor_ip: Option<String>,
+}
+
+Note: the crate using the derive must depend on typetag and serde.
+*/
+#[proc_macro_derive(Policy, attributes(allow_file, allow_remote_ip, allow_other, deny_other))]
+pub fn policy_derive(input: TokenStream) -> TokenStream {
+  let input = syn::parse_macro_input!(input as syn::DeriveInput);
+  expand_policy(&input)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+// A single #[allow_file(...)] or #[allow_remote_ip(...)] rule.
+struct AllowRule {
+  // For files, whether the field names the file or a directory containing it.
+  by_path: bool,
+  field: syn::Ident,
+  message: Option<syn::LitStr>,
+}
+
+fn expand_policy(input: &syn::DeriveInput) -> syn::Result<TokenStream2> {
+  let name = &input.ident;
+  let field_names: Vec<syn::Ident> = match &input.data {
+    syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) =>
+      fields.named.iter().filter_map(|f| f.ident.clone()).collect(),
+    syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unit, .. }) => vec![],
+    _ => return Err(syn::Error::new(
+      name.span(), "#[derive(Policy)] requires a struct with named fields"
+    )),
+  };
+
+  let mut file_rules: Vec<AllowRule> = vec![];
+  let mut ip_rules: Vec<AllowRule> = vec![];
+  let mut deny_other: Option<Option<syn::LitStr>> = None;
+  let mut allow_other: Option<&syn::Attribute> = None;
+  for attr in input.attrs.iter() {
+    if attr.path().is_ident("allow_file") {
+      // Each `name = FIELD` or `path = FIELD` is a rule of its own.
+      let mut rules: Vec<AllowRule> = vec![];
+      let mut message = None;
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("message") {
+          message = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("name") || meta.path.is_ident("path") {
+          let by_path = meta.path.is_ident("path");
+          let field: syn::Ident = meta.value()?.parse()?;
+          rules.push(AllowRule { by_path, field, message: None });
+        } else {
+          return Err(meta.error("expected `name = FIELD`, `path = FIELD` or `message = \"...\"`"));
+        }
+        Ok(())
+      })?;
+      if rules.is_empty() {
+        return Err(syn::Error::new_spanned(attr, "#[allow_file] needs `name = FIELD` or `path = FIELD`"));
+      }
+      file_rules.extend(rules.into_iter().map(|rule| AllowRule { message: message.clone(), ..rule }));
+    } else if attr.path().is_ident("allow_remote_ip") {
+      let mut fields: Vec<syn::Ident> = vec![];
+      let mut message = None;
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("message") {
+          message = Some(meta.value()?.parse()?);
+        } else {
+          fields.push(meta.path.require_ident()?.clone());
+        }
+        Ok(())
+      })?;
+      if fields.is_empty() {
+        return Err(syn::Error::new_spanned(
+          attr, "#[allow_remote_ip] needs the field holding the allowed address"
+        ));
+      }
+      ip_rules.extend(fields.into_iter().map(|field| AllowRule { by_path: false, field, message: message.clone() }));
+    } else if attr.path().is_ident("allow_other") {
+      if let syn::Meta::List(_) | syn::Meta::NameValue(_) = &attr.meta {
+        return Err(syn::Error::new_spanned(attr, "#[allow_other] takes no arguments"));
+      }
+      if allow_other.is_some() {
+        return Err(syn::Error::new_spanned(attr, "duplicate #[allow_other] attribute"));
+      }
+      allow_other = Some(attr);
+    } else if attr.path().is_ident("deny_other") {
+      if deny_other.is_some() {
+        return Err(syn::Error::new_spanned(attr, "duplicate #[deny_other] attribute"));
+      }
+      let mut message = None;
+      if let syn::Meta::List(_) = &attr.meta {
+        attr.parse_nested_meta(|meta| {
+          if meta.path.is_ident("message") {
+            message = Some(meta.value()?.parse()?);
+            Ok(())
+          } else {
+            Err(meta.error("expected `message = \"...\"`"))
+          }
+        })?;
+      }
+      deny_other = Some(message);
+    }
+  }
+
+  if let (Some(attr), Some(_)) = (allow_other, &deny_other) {
+    return Err(syn::Error::new_spanned(attr, "#[allow_other] conflicts with #[deny_other]"));
+  }
+
+  // Every rule must refer to a field of the struct.
+  for rule in file_rules.iter().chain(ip_rules.iter()) {
+    if !field_names.contains(&rule.field) {
+      return Err(syn::Error::new(rule.field.span(), format!("`{}` has no field `{}`", name, rule.field)));
+    }
+  }
+
+  let first_message = |rules: &[AllowRule], default: String| {
+    rules.iter().find_map(|r| r.message.as_ref().map(|m| m.value())).unwrap_or(default)
+  };
+
+  /*
+  Generate the File arm.
+  Example:
+  ```
+  Context::File(fc) => {
+    if self.student_id.any_matches(&|v| v == fc.file_name) || ... { Ok(()) } else { Err(...) }
+  }
+  ```
+  */
+  let file_arm = if file_rules.is_empty() {
+    quote!()
+  } else {
+    let conditions = file_rules.iter().map(|r| {
+      let field = &r.field;
+      if r.by_path {
+        quote!(beaver::policy::PolicyValue::any_matches(&self.#field, &|v| fc.is_within(v)))
+      } else {
+        quote!(beaver::policy::PolicyValue::any_matches(&self.#field, &|v| v == fc.file_name))
+      }
     });
-  
-  // Putting it all together! 
-  let expanded_derive = quote! {
-    impl #policied_name {
-      #make_decomposed
-      #expanded_protected
+    let message = first_message(&file_rules, format!("{} does not allow writing to this file", name));
+    quote! {
+      beaver::filter::Context::File(fc) => {
+        if #(#conditions)||* {
+          Ok(())
+        } else {
+          Err(beaver::policy::PolicyError { message: #message.to_string() })
+        }
+      },
     }
   };
 
-  TokenStream::from(expanded_derive)
+  let ip_arm = if ip_rules.is_empty() {
+    quote!()
+  } else {
+    let conditions = ip_rules.iter().map(|r| {
+      let field = &r.field;
+      quote!(beaver::policy::PolicyValue::any_matches(&self.#field, &|v| v == ip))
+    });
+    let message = first_message(&ip_rules, format!("{} does not allow sending to this IP address", name));
+    quote! {
+      beaver::filter::Context::ClientNetwork(rcc) => {
+        let ip = rcc.remote_ip_address.to_string();
+        if #(#conditions)||* {
+          Ok(())
+        } else {
+          Err(beaver::policy::PolicyError { message: #message.to_string() })
+        }
+      },
+    }
+  };
+
+  // Anything not matched above is denied unless the policy opts out with #[allow_other].
+  let other_arm = if allow_other.is_some() {
+    quote!(_ => Ok(()),)
+  } else {
+    let message = deny_other.flatten().map(|m| m.value())
+      .unwrap_or_else(|| format!("{} does not allow this flow", name));
+    quote!(_ => Err(beaver::policy::PolicyError { message: #message.to_string() }),)
+  };
+
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    #[typetag::serde]
+    impl #impl_generics beaver::policy::Policy for #name #ty_generics #where_clause {
+      fn check(&self, ctxt: &beaver::filter::Context) -> Result<(), beaver::policy::PolicyError> {
+        match ctxt {
+          beaver::filter::Context::Purpose(pc) => beaver::policy::Policy::check(self, &pc.inner),
+          beaver::filter::Context::Composite(_) => beaver::policy::Policy::check_flow(self, ctxt),
+          #file_arm
+          #ip_arm
+          #other_arm
+        }
+      }
+
+      fn merge(&self, other: &Box<dyn beaver::policy::Policy>) -> Result<Box<dyn beaver::policy::Policy>, beaver::policy::PolicyError> {
+        Ok(Box::new(beaver::policy::MergePolicy::make(
+          Box::new(self.clone()),
+          other.clone(),
+        )))
+      }
+    }
+  })
 }
@@ -0,0 +1,107 @@
+/*
+Async counterparts of BeaverBufWriter and BeaverBufReader for tokio, available with
+the `tokio` feature. They perform the same policy checks and read and write the same
+newline-delimited formats, so data written by one can be read by the other.
+
+Usage:
+let mut bw = AsyncBeaverWriter::safe_create(stream, ctxt);
+bw.safe_write_json(&grade).await?;
+bw.flush().await?;
+*/
+use std::error::Error;
+use std::io;
+
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::TcpStream;
+
+use crate::filter;
+use crate::policy;
+use crate::policy::Policied;
+
+pub struct AsyncBeaverWriter<W: AsyncWrite + Unpin> {
+    buf_writer: BufWriter<W>,
+    ctxt: filter::Context,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncBeaverWriter<W> {
+    pub fn safe_create(inner: W, context: filter::Context) -> AsyncBeaverWriter<W> {
+        AsyncBeaverWriter {
+            buf_writer: BufWriter::new(inner),
+            ctxt: context,
+        }
+    }
+
+    pub fn safe_create_with_purpose(inner: W, context: filter::Context, purpose: &str) -> AsyncBeaverWriter<W> {
+        AsyncBeaverWriter::safe_create(inner, context.with_purpose(purpose))
+    }
+
+    // Fails with InvalidInput if `hops` is empty, like BeaverBufWriter::safe_create_multi_hop.
+    pub fn safe_create_multi_hop(inner: W, hops: Vec<filter::Context>) -> io::Result<AsyncBeaverWriter<W>> {
+        if hops.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a multi-hop flow needs at least one hop"));
+        }
+        Ok(AsyncBeaverWriter::safe_create(inner, filter::Context::chain(hops)))
+    }
+
+    pub fn purpose(&self) -> Option<&str> {
+        self.ctxt.purpose()
+    }
+
+    pub async fn safe_write_serialized(&mut self, buf: &policy::PoliciedString) -> Result<usize, Box<dyn Error>> {
+        let s = buf.export_check_borrow(&self.ctxt)?;
+        let line = format!("{}\n", s);
+        self.buf_writer.write_all(line.as_bytes()).await?;
+        Ok(line.len())
+    }
+
+    pub async fn safe_write_json<T, P: Policied<T> + serde::Serialize>(&mut self, buf: &P)
+    -> Result<usize, Box<dyn Error>> {
+        buf.get_policy().check_flow(&self.ctxt)?;
+        let line = format!("{}\n", serde_json::to_string(buf)?);
+        self.buf_writer.write_all(line.as_bytes()).await?;
+        Ok(line.len())
+    }
+
+    // Unlike the blocking writer, buffered data is not written out on drop.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.buf_writer.flush().await
+    }
+
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.buf_writer.shutdown().await
+    }
+}
+
+impl AsyncBeaverWriter<TcpStream> {
+    // The context is filled in from the address of the connected peer.
+    pub fn safe_create_tcp(stream: TcpStream) -> io::Result<AsyncBeaverWriter<TcpStream>> {
+        let peer = stream.peer_addr()?;
+        let ctxt = filter::Context::ClientNetwork(filter::RemoteConnectContext {
+            remote_ip_address: peer.ip(),
+            port: peer.port(),
+        });
+        Ok(AsyncBeaverWriter::safe_create(stream, ctxt))
+    }
+}
+
+pub struct AsyncBeaverReader<R: AsyncRead + Unpin> {
+    buf_reader: BufReader<R>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncBeaverReader<R> {
+    pub fn safe_create(inner: R) -> AsyncBeaverReader<R> {
+        AsyncBeaverReader {
+            buf_reader: BufReader::new(inner)
+        }
+    }
+
+    // Reads one line written by safe_write_json. Returns None at end of stream.
+    pub async fn safe_deserialize_line<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.buf_reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
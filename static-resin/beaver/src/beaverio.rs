@@ -1,5 +1,11 @@
 use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
 use std::io::{BufWriter, Write, BufReader, Read, BufRead};
+use std::path::Path;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 
 use serde::de::DeserializeOwned;
 
@@ -10,11 +16,62 @@ use crate::policy::{Policied, PolicyError};
 extern crate serde;
 
 pub fn export_and_release(context: &filter::Context, s: &policy::PoliciedString) -> Result<String, Box<PolicyError>> {
-    match s.export_check_borrow(&context) {
+    match s.export_check_borrow(context) {
         Ok(str) => { Ok(str.clone()) }, 
         Err(pe) => { Err(Box::new(pe)) }
     }
 }
+pub const REDACTED: &str = "[REDACTED]";
+
+// Renders a policied value for display in `ctxt`, or REDACTED if its policy forbids it.
+pub fn display_or_redact<T: fmt::Display, P: Policied<T>>(value: &P, ctxt: &filter::Context) -> String {
+    match value.export_check_borrow(ctxt) {
+        Ok(v) => v.to_string(),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/*
+Policy-checked versions of print!, println!, eprint! and eprintln!. Every argument 
+must be a policied value; each one is checked against the console context and 
+replaced by REDACTED if it may not be shown.
+
+Usage: beaver_println!("{} got {}", student_id, grade);
+*/
+#[macro_export]
+macro_rules! beaver_print {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let ctxt = $crate::filter::Context::Console($crate::filter::ConsoleContext::Stdout);
+        print!($fmt $(, $crate::beaverio::display_or_redact(&$arg, &ctxt))*);
+    }};
+}
+
+#[macro_export]
+macro_rules! beaver_println {
+    () => { println!() };
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let ctxt = $crate::filter::Context::Console($crate::filter::ConsoleContext::Stdout);
+        println!($fmt $(, $crate::beaverio::display_or_redact(&$arg, &ctxt))*);
+    }};
+}
+
+#[macro_export]
+macro_rules! beaver_eprint {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let ctxt = $crate::filter::Context::Console($crate::filter::ConsoleContext::Stderr);
+        eprint!($fmt $(, $crate::beaverio::display_or_redact(&$arg, &ctxt))*);
+    }};
+}
+
+#[macro_export]
+macro_rules! beaver_eprintln {
+    () => { eprintln!() };
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let ctxt = $crate::filter::Context::Console($crate::filter::ConsoleContext::Stderr);
+        eprintln!($fmt $(, $crate::beaverio::display_or_redact(&$arg, &ctxt))*);
+    }};
+}
+
 // TODO: Add just an export_check funciton that takes in: PoliciedString, Context, and returns the raw string
 // Rationale: We need to make Beaver be able to work with other libraries (such as lettre::Email)
 
@@ -31,6 +88,24 @@ impl<W: Write> BeaverBufWriter<W> {
         }
     }
 
+    // Like safe_create, but every write is checked against `context` tagged with `purpose`.
+    pub fn safe_create_with_purpose(inner: W, context: filter::Context, purpose: &str) -> BeaverBufWriter<W> {
+        BeaverBufWriter::safe_create(inner, context.with_purpose(purpose))
+    }
+
+    // For flows that pass through several sinks before reaching `inner`'s destination.
+    // Fails with InvalidInput if `hops` is empty.
+    pub fn safe_create_multi_hop(inner: W, hops: Vec<filter::Context>) -> io::Result<BeaverBufWriter<W>> {
+        if hops.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "a multi-hop flow needs at least one hop"));
+        }
+        Ok(BeaverBufWriter::safe_create(inner, filter::Context::chain(hops)))
+    }
+
+    pub fn purpose(&self) -> Option<&str> {
+        self.ctxt.purpose()
+    }
+
     pub fn safe_write_serialized(&mut self, buf: &policy::PoliciedString) -> Result<usize, Box<dyn Error>> {
         match buf.export_check_borrow(&self.ctxt) {
             Ok(s) => {
@@ -43,11 +118,14 @@ impl<W: Write> BeaverBufWriter<W> {
         }
     }
 
-    pub fn safe_write_json<T, P: Policied<T> + serde::Serialize>(&mut self, buf: &Box<P>)
+    // Accepts any serializable policied value, e.g. a GPolicied<T>, a derived policied 
+    // struct, or a Box of either.
+    pub fn safe_write_json<T, P: Policied<T> + serde::Serialize>(&mut self, buf: &P)
     -> Result<usize, Box<dyn Error>> {
-        match buf.get_policy().check(&self.ctxt) {
+        match buf.get_policy().check_flow(&self.ctxt) {
             Ok(_) => { 
-                match self.buf_writer.write(format!("{}\n", serde_json::to_string(&*buf).unwrap()).as_bytes()) {
+                let json = serde_json::to_string(buf)?;
+                match self.buf_writer.write(format!("{}\n", json).as_bytes()) {
                     Ok(s) => { Ok(s) },
                     Err(e) => { Err(Box::new(e)) }
                 }
@@ -69,6 +147,30 @@ impl<W: Write> BeaverBufWriter<W> {
     // Possible extension: Add other safe serialize methods (xml, other formats)
 }
 
+#[cfg(unix)]
+impl BeaverBufWriter<UnixStream> {
+    // The context is filled in from the socket's peer address and credentials.
+    pub fn safe_create_unix(stream: UnixStream) -> io::Result<BeaverBufWriter<UnixStream>> {
+        let ctxt = filter::UnixSocketContext::from_stream(&stream)?;
+        Ok(BeaverBufWriter::safe_create(stream, filter::Context::UnixSocket(ctxt)))
+    }
+
+    pub fn safe_connect_unix<P: AsRef<Path>>(path: P) -> io::Result<BeaverBufWriter<UnixStream>> {
+        BeaverBufWriter::safe_create_unix(UnixStream::connect(path)?)
+    }
+}
+
+#[cfg(unix)]
+impl BeaverBufWriter<File> {
+    // Opens an existing named pipe for writing. Blocks until a reader opens the other end.
+    // The context is built from the opened file, see PipeContext::from_file.
+    pub fn safe_create_pipe<P: AsRef<Path>>(path: P) -> io::Result<BeaverBufWriter<File>> {
+        let pipe = std::fs::OpenOptions::new().write(true).open(&path)?;
+        let ctxt = filter::PipeContext::from_file(&pipe, &path)?;
+        Ok(BeaverBufWriter::safe_create(pipe, filter::Context::Pipe(ctxt)))
+    }
+}
+
 // TODO: Does a BufReader need a context? Or do we assume that any data we're reading in has been approved to flow here?
 pub struct BeaverBufReader<R: Read> {
     buf_reader: BufReader<R>,
@@ -86,4 +188,53 @@ impl<R: Read> BeaverBufReader<R> {
         self.buf_reader.read_line(&mut deserialized_string).unwrap(); // TODO: handle this 
         serde_json::from_reader(deserialized_string.as_bytes()).expect("Unable to deserialize data")
     }
-}
\ No newline at end of file
+}
+
+/*
+A file whose FileContext is derived from where it actually lives on disk, rather 
+than from strings typed in by the caller. 
+
+Usage: 
+let mut bw = BeaverFile::create("grades/malte.json")?.into_writer();
+*/
+pub struct BeaverFile {
+    file: File,
+    ctxt: filter::FileContext,
+}
+
+impl BeaverFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<BeaverFile> {
+        let file = File::create(&path)?;
+        let ctxt = filter::FileContext::from_file(&file, &path)?;
+        Ok(BeaverFile { file, ctxt })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<BeaverFile> {
+        let file = File::open(&path)?;
+        let ctxt = filter::FileContext::from_file(&file, &path)?;
+        Ok(BeaverFile { file, ctxt })
+    }
+
+    pub fn context(&self) -> &filter::FileContext {
+        &self.ctxt
+    }
+
+    pub fn into_writer(self) -> BeaverBufWriter<File> {
+        BeaverBufWriter::safe_create(self.file, filter::Context::File(self.ctxt))
+    }
+
+    pub fn into_writer_with_purpose(self, purpose: &str) -> BeaverBufWriter<File> {
+        BeaverBufWriter::safe_create_with_purpose(self.file, filter::Context::File(self.ctxt), purpose)
+    }
+
+    // Writes to this file after the data has passed through `hops`, e.g. the network 
+    // hop to an NFS server; the file itself is the last hop.
+    pub fn into_writer_via(self, mut hops: Vec<filter::Context>) -> BeaverBufWriter<File> {
+        hops.push(filter::Context::File(self.ctxt));
+        BeaverBufWriter::safe_create(self.file, filter::Context::chain(hops))
+    }
+
+    pub fn into_reader(self) -> BeaverBufReader<File> {
+        BeaverBufReader::safe_create(self.file)
+    }
+}
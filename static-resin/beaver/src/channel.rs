@@ -0,0 +1,130 @@
+/*
+Policied channels for passing policied values between threads and tasks. The sender
+is bound to the context of the receiving end (e.g. Context::Channel naming the
+subsystem or principal) and checks every value's policy against it before the value
+is enqueued, just as BeaverBufWriter does for files and sockets. Values arrive still
+policied, so the receiver remains subject to their policies.
+
+Usage:
+let (tx, rx) = channel::channel(Context::Channel(ChannelContext { name: "grader".to_string() }));
+tx.send(grade)?;
+*/
+use std::error::Error;
+use std::fmt;
+use std::sync::{mpsc, Arc};
+
+use crate::filter;
+use crate::policy::{Policied, PolicyError};
+
+pub struct PoliciedSender<S> {
+    sender: S,
+    ctxt: Arc<filter::Context>,
+}
+
+// Clones share the context.
+impl<S: Clone> Clone for PoliciedSender<S> {
+    fn clone(&self) -> Self {
+        PoliciedSender { sender: self.sender.clone(), ctxt: self.ctxt.clone() }
+    }
+}
+
+// The value that could not be sent is handed back in either case.
+pub enum PoliciedSendError<P> {
+    Denied(P, PolicyError),
+    Disconnected(P),
+}
+
+impl<P> fmt::Display for PoliciedSendError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoliciedSendError::Denied(_, pe) => write!(f, "{}", pe),
+            PoliciedSendError::Disconnected(_) => write!(f, "sending on a closed channel"),
+        }
+    }
+}
+
+// Policied values have no Debug impl, and the value must not be printed anyway.
+impl<P> fmt::Debug for PoliciedSendError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoliciedSendError::Denied(_, pe) => f.debug_tuple("Denied").field(&"..").field(pe).finish(),
+            PoliciedSendError::Disconnected(_) => f.debug_tuple("Disconnected").field(&"..").finish(),
+        }
+    }
+}
+
+impl<P> Error for PoliciedSendError<P> {}
+
+impl<S> PoliciedSender<S> {
+    // Wraps an existing sender whose receiving end is described by `context`.
+    pub fn new(sender: S, context: filter::Context) -> PoliciedSender<S> {
+        PoliciedSender { sender, ctxt: Arc::new(context) }
+    }
+
+    pub fn context(&self) -> &filter::Context {
+        &self.ctxt
+    }
+
+    fn check<T, P: Policied<T>>(&self, value: P) -> Result<P, PoliciedSendError<P>> {
+        match value.get_policy().check_flow(&self.ctxt) {
+            Ok(_) => Ok(value),
+            Err(pe) => Err(PoliciedSendError::Denied(value, pe)),
+        }
+    }
+}
+
+impl<P> PoliciedSender<mpsc::Sender<P>> {
+    pub fn send<T>(&self, value: P) -> Result<(), PoliciedSendError<P>> where P: Policied<T> {
+        let value = self.check(value)?;
+        self.sender.send(value).map_err(|e| PoliciedSendError::Disconnected(e.0))
+    }
+}
+
+impl<P> PoliciedSender<mpsc::SyncSender<P>> {
+    // Blocks while the channel is full.
+    pub fn send<T>(&self, value: P) -> Result<(), PoliciedSendError<P>> where P: Policied<T> {
+        let value = self.check(value)?;
+        self.sender.send(value).map_err(|e| PoliciedSendError::Disconnected(e.0))
+    }
+}
+
+pub fn channel<P>(context: filter::Context) -> (PoliciedSender<mpsc::Sender<P>>, mpsc::Receiver<P>) {
+    let (tx, rx) = mpsc::channel();
+    (PoliciedSender::new(tx, context), rx)
+}
+
+pub fn sync_channel<P>(context: filter::Context, bound: usize) -> (PoliciedSender<mpsc::SyncSender<P>>, mpsc::Receiver<P>) {
+    let (tx, rx) = mpsc::sync_channel(bound);
+    (PoliciedSender::new(tx, context), rx)
+}
+
+#[cfg(feature = "tokio")]
+impl<P> PoliciedSender<tokio::sync::mpsc::Sender<P>> {
+    // Waits for capacity; the policy is checked before waiting.
+    pub async fn send<T>(&self, value: P) -> Result<(), PoliciedSendError<P>> where P: Policied<T> {
+        let value = self.check(value)?;
+        self.sender.send(value).await.map_err(|e| PoliciedSendError::Disconnected(e.0))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<P> PoliciedSender<tokio::sync::mpsc::UnboundedSender<P>> {
+    pub fn send<T>(&self, value: P) -> Result<(), PoliciedSendError<P>> where P: Policied<T> {
+        let value = self.check(value)?;
+        self.sender.send(value).map_err(|e| PoliciedSendError::Disconnected(e.0))
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub fn tokio_channel<P>(context: filter::Context, buffer: usize)
+-> (PoliciedSender<tokio::sync::mpsc::Sender<P>>, tokio::sync::mpsc::Receiver<P>) {
+    let (tx, rx) = tokio::sync::mpsc::channel(buffer);
+    (PoliciedSender::new(tx, context), rx)
+}
+
+#[cfg(feature = "tokio")]
+pub fn tokio_unbounded_channel<P>(context: filter::Context)
+-> (PoliciedSender<tokio::sync::mpsc::UnboundedSender<P>>, tokio::sync::mpsc::UnboundedReceiver<P>) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    (PoliciedSender::new(tx, context), rx)
+}
@@ -1,36 +1,360 @@
 use std::net::{IpAddr};
 use std::any::Any;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 
 pub enum Context {
     File(FileContext),
     ClientNetwork(RemoteConnectContext),
     ServerNetwork(ListenConnectionsContext),
+    UnixSocket(UnixSocketContext),
+    Pipe(PipeContext),
+    Console(ConsoleContext),
+    Channel(ChannelContext),
+    Purpose(PurposeContext),
+    Composite(CompositeContext),
     KVContext(std::collections::HashMap<String, String>),
-    CustomContext(Box<dyn Any + 'static>)
+    TypedKVContext(TypedKVContext),
+    CustomContext(Box<dyn Any + Send + Sync + 'static>)
+}
+
+impl Context {
+    // Tags this context with the purpose the data is being released for. On a 
+    // composite context, every hop is tagged.
+    pub fn with_purpose(self, purpose: &str) -> Context {
+        match self {
+            Context::Composite(cc) => Context::Composite(CompositeContext {
+                hops: cc.hops.into_iter().map(|h| h.with_purpose(purpose)).collect(),
+            }),
+            ctxt => Context::Purpose(PurposeContext { purpose: purpose.to_string(), inner: Box::new(ctxt) }),
+        }
+    }
+
+    /*
+    Builds a context for a flow that passes through several sinks, in order. Nested 
+    composites are flattened and a single hop is returned as is. Panics if there are 
+    no hops, since such a flow has no sink a policy could check.
+
+    Usage: Context::chain(vec![Context::ClientNetwork(proxy), Context::File(fc)])
+    */
+    pub fn chain(hops: Vec<Context>) -> Context {
+        let mut flat = Vec::new();
+        for hop in hops {
+            match hop {
+                Context::Composite(cc) => flat.extend(cc.hops),
+                ctxt => flat.push(ctxt),
+            }
+        }
+        assert!(!flat.is_empty(), "Context::chain needs at least one hop");
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            Context::Composite(CompositeContext { hops: flat })
+        }
+    }
+
+    // Appends `next` as the following hop of this flow.
+    pub fn then(self, next: Context) -> Context {
+        Context::chain(vec![self, next])
+    }
+
+    // The individual sinks of this flow; a non-composite context is its only hop.
+    pub fn hops(&self) -> Vec<&Context> {
+        match self {
+            Context::Composite(cc) => cc.hops.iter().collect(),
+            _ => vec![self],
+        }
+    }
+
+    // The outermost purpose attached to this context, if any. A composite context has 
+    // a purpose only if every hop carries the same one.
+    pub fn purpose(&self) -> Option<&str> {
+        match self {
+            Context::Purpose(pc) => Some(&pc.purpose),
+            Context::Composite(cc) => {
+                let mut purposes = cc.hops.iter().map(|h| h.purpose());
+                let first = purposes.next()??;
+                if purposes.all(|p| p == Some(first)) { Some(first) } else { None }
+            },
+            _ => None,
+        }
+    }
+
+    // The underlying sink with all purpose tags removed.
+    pub fn without_purpose(&self) -> &Context {
+        match self {
+            Context::Purpose(pc) => pc.inner.without_purpose(),
+            _ => self,
+        }
+    }
 }
 
 pub trait CustomContext {
     fn as_any(&self) -> &dyn Any;
 }
 
-// Possible extension: infer from file object? 
 pub struct FileContext {
     pub file_name: String,
     pub path: String,
 }
 
+impl FileContext {
+    /* 
+    Builds a FileContext from the real location of a file on disk: the path is 
+    canonicalized (symlinks and `..` resolved), `file_name` is its last component 
+    and `path` is the directory containing it. The file must already exist.
+    */
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<FileContext> {
+        let canonical = path.as_ref().canonicalize()?;
+        let file_name = canonical.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dir = canonical.parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(FileContext { file_name, path: dir })
+    }
+
+    /*
+    Builds a FileContext for a file that is already open. As with PipeContext::from_file,
+    `path` must still name that same file (same device and inode) once canonicalized, so
+    the context describes the file that is actually written and not whatever the path
+    pointed to before or after the open.
+    */
+    #[cfg(unix)]
+    pub fn from_file<P: AsRef<Path>>(file: &std::fs::File, path: P) -> io::Result<FileContext> {
+        use std::os::unix::fs::MetadataExt;
+        let ctxt = FileContext::from_path(path)?;
+        let opened = file.metadata()?;
+        let named = ctxt.full_path().metadata()?;
+        if named.dev() != opened.dev() || named.ino() != opened.ino() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "path no longer names the opened file"));
+        }
+        Ok(ctxt)
+    }
+
+    // Without device and inode numbers the path can only be resolved, not compared.
+    #[cfg(not(unix))]
+    pub fn from_file<P: AsRef<Path>>(_file: &std::fs::File, path: P) -> io::Result<FileContext> {
+        FileContext::from_path(path)
+    }
+
+    pub fn full_path(&self) -> PathBuf {
+        Path::new(&self.path).join(&self.file_name)
+    }
+
+    // True if the file lives somewhere below `dir` (compared component-wise, so 
+    // "/srv/data" does not match "/srv/database/x").
+    pub fn is_within<P: AsRef<Path>>(&self, dir: P) -> bool {
+        let dir = dir.as_ref();
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.full_path().starts_with(dir)
+    }
+
+    // Matches the full path against a shell-style glob, e.g. "/srv/grades/**/*.json".
+    // Invalid patterns never match.
+    pub fn matches_glob(&self, pattern: &str) -> bool {
+        match glob::Pattern::new(pattern) {
+            Ok(p) => p.matches_path_with(&self.full_path(), glob::MatchOptions {
+                case_sensitive: true,
+                require_literal_separator: true,
+                require_literal_leading_dot: false,
+            }),
+            Err(_) => false,
+        }
+    }
+}
+
 pub struct RemoteConnectContext {
     pub remote_ip_address: IpAddr,
     pub port: u16,
 }
 
+pub struct UnixSocketContext {
+    pub socket_path: Option<String>,
+    pub peer: Option<PeerCredentials>,
+}
+
+// Credentials of the process on the other end of a Unix socket. The pid is not 
+// available on every platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+#[cfg(unix)]
+impl UnixSocketContext {
+    /*
+    Builds a UnixSocketContext for a connected stream, using the path the peer is 
+    bound to (None for unnamed/abstract sockets) and the peer's credentials as 
+    reported by the kernel.
+    */
+    pub fn from_stream(stream: &UnixStream) -> io::Result<UnixSocketContext> {
+        let socket_path = stream.peer_addr()?
+            .as_pathname()
+            .map(|p| p.to_string_lossy().into_owned());
+        Ok(UnixSocketContext { socket_path, peer: Some(peer_credentials(stream)?) })
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    use std::os::unix::io::AsRawFd;
+    let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(PeerCredentials { uid: cred.uid, gid: cred.gid, pid: Some(cred.pid) })
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    use std::os::unix::io::AsRawFd;
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(PeerCredentials { uid, gid, pid: None })
+}
+
+// A named pipe (FIFO) on the filesystem.
+pub struct PipeContext {
+    pub path: String,
+}
+
+#[cfg(unix)]
+impl PipeContext {
+    // Fails if `path` does not exist or is not a FIFO.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<PipeContext> {
+        use std::os::unix::fs::FileTypeExt;
+        let canonical = path.as_ref().canonicalize()?;
+        if !canonical.metadata()?.file_type().is_fifo() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a named pipe"));
+        }
+        Ok(PipeContext { path: canonical.to_string_lossy().into_owned() })
+    }
+
+    /*
+    Builds a PipeContext for a pipe that is already open. The type is taken from the 
+    open file itself, and `path` must still name that same file (same device and inode) 
+    once canonicalized, so the pipe cannot be swapped out between the check and the open.
+    */
+    pub fn from_file<P: AsRef<Path>>(file: &std::fs::File, path: P) -> io::Result<PipeContext> {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        let opened = file.metadata()?;
+        if !opened.file_type().is_fifo() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a named pipe"));
+        }
+        let canonical = path.as_ref().canonicalize()?;
+        let named = canonical.metadata()?;
+        if named.dev() != opened.dev() || named.ino() != opened.ino() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "path no longer names the opened pipe"));
+        }
+        Ok(PipeContext { path: canonical.to_string_lossy().into_owned() })
+    }
+}
+
+// The terminal streams of the current process, see beaver_print! and friends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleContext {
+    Stdout,
+    Stderr,
+}
+
+// An in-process channel, named after the subsystem or principal on the receiving end.
+// See channel::channel.
+pub struct ChannelContext {
+    pub name: String,
+}
+
+// Wraps another context with the purpose of the flow, e.g. "billing" or "marketing".
+pub struct PurposeContext {
+    pub purpose: String,
+    pub inner: Box<Context>,
+}
+
+// A flow through several sinks (e.g. a proxy hop and then a file), see Context::chain.
+// Policy::check_flow requires the policy to allow every hop.
+pub struct CompositeContext {
+    pub hops: Vec<Context>,
+}
+
 // TODO: Flesh out use case for this; do we need this? 
 pub struct ListenConnectionsContext {
     _ip_address: IpAddr,
 }
 
+/*
+A key into a TypedKVContext. Keys are declared once with the type of their value, 
+so a misspelled key is a compile error and values never need to be re-parsed.
 
+Usage: pub const USER_ID: CtxKey<u64> = CtxKey::new("user_id");
+*/
+pub struct CtxKey<T> {
+    name: &'static str,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> CtxKey<T> {
+    pub const fn new(name: &'static str) -> CtxKey<T> {
+        CtxKey { name, _value: PhantomData }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+#[derive(Default)]
+pub struct TypedKVContext {
+    values: HashMap<&'static str, Box<dyn Any + Send + Sync>>,
+}
+
+impl TypedKVContext {
+    pub fn new() -> TypedKVContext {
+        TypedKVContext::default()
+    }
 
+    pub fn insert<T: Any + Send + Sync>(&mut self, key: &CtxKey<T>, value: T) {
+        self.values.insert(key.name, Box::new(value));
+    }
+
+    // None if the key is absent or was inserted through a key of a different type.
+    pub fn get<T: Any>(&self, key: &CtxKey<T>) -> Option<&T> {
+        self.values.get(key.name).and_then(|v| v.downcast_ref())
+    }
+
+    pub fn contains<T: Any>(&self, key: &CtxKey<T>) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+/*
+Builds a key-value context. With string literal keys every value is stringified 
+into a KVContext; with CtxKey constants as keys a TypedKVContext is built and each 
+value is type-checked against its key.
+
+Usage: 
+kv_ctx!("user_id" => 42, "ip" => addr);
+kv_ctx!(USER_ID => 42, REMOTE_IP => addr);
+*/
 #[macro_export]
 macro_rules! kv_ctx {
     ($($k:literal => $v:expr),* $(,)?) => {{
@@ -39,5 +363,10 @@ macro_rules! kv_ctx {
         ;
         $crate::filter::Context::KVContext(m)
     }};
+    ($($k:path => $v:expr),+ $(,)?) => {{
+        let mut m = $crate::filter::TypedKVContext::new();
+        $(m.insert(&$k, $v);)+
+        $crate::filter::Context::TypedKVContext(m)
+    }};
 }
 
@@ -1,7 +1,7 @@
-use std::collections::HashMap;
-use crate::policy::{Policied, NonePolicy, Policy, PolicyError};
+use std::collections::{BTreeMap, HashMap};
+use crate::policy::{Policied, HasPolicied, NonePolicy, Policy, PolicyError, SharedPolicy};
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct GPolicied<T> {
     inner: T,
     policy: Box<dyn Policy>
@@ -28,12 +28,18 @@ impl <T> GPolicied<T> {
         GPolicied::make(inner(x), policy.merge(&p2).unwrap())
     }
 
-    pub fn map<V, F: Fn(T) -> V>(self, f: F) -> GPolicied<V> 
+    pub fn map<V, F: FnOnce(T) -> V>(self, f: F) -> GPolicied<V> 
     {
         let GPolicied { inner, policy } = self;
         GPolicied::make(f(inner), policy)
     }
 
+    // Moves the policy behind an Arc (see SharedPolicy), making clones of this value cheap.
+    pub fn share_policy(self) -> Self {
+        let GPolicied { inner, policy } = self;
+        GPolicied::make(inner, SharedPolicy::make(policy))
+    }
+
     pub fn unsafe_into_inner(self) -> T {
         self.inner
     }
@@ -105,21 +111,70 @@ impl <T> InternalizePolicy for GPolicied<Vec<T>> {
     }
 }
 
-pub trait InternalizePolicy_2_1 {
-    type Result;
-    fn internalize_policy_2_1(self) -> Self::Result;
+impl <T> InternalizePolicy for GPolicied<Option<T>> {
+    type Result = Option<GPolicied<T>>;
+    fn internalize_policy(self) -> Self::Result {
+        self.get_option()
+    }
 }
 
-impl <K,V> InternalizePolicy_2_1 for GPolicied<HashMap<K,V>> 
-where K: Eq + std::hash::Hash
-{
-    type Result = HashMap<K, GPolicied<V>>;
-    fn internalize_policy_2_1(self) -> Self::Result {
-        let (inner, policy) = self.unsafe_decompose();
-        inner.into_iter().map(|(k, v)| (k, v.policied_with(policy.clone()))).collect()
+impl <T, E> ExternalizePolicy for Result<GPolicied<T>, GPolicied<E>> {
+    type Result = GPolicied<Result<T, E>>;
+    fn externalize_policy(self) -> Self::Result {
+        match self {
+            Ok(p) => p.map(Ok),
+            Err(p) => p.map(Err),
+        }
+    }
+}
+
+impl <T, E> InternalizePolicy for GPolicied<Result<T, E>> {
+    type Result = Result<GPolicied<T>, GPolicied<E>>;
+    fn internalize_policy(self) -> Self::Result {
+        let GPolicied { inner, policy } = self;
+        match inner {
+            Ok(v) => Ok(GPolicied::make(v, policy)),
+            Err(e) => Err(GPolicied::make(e, policy)),
+        }
     }
 }
 
+// A tuple of policied values becomes one policied tuple carrying all of their 
+// policies; in the other direction every component gets the tuple's policy.
+macro_rules! gpolicied_tuple {
+    ($($t:ident $v:ident),+) => {
+        impl <$($t),+> ExternalizePolicy for ($(GPolicied<$t>,)+) {
+            type Result = GPolicied<($($t,)+)>;
+            fn externalize_policy(self) -> Self::Result {
+                let ($($v,)+) = self;
+                let mut policy: Box<dyn Policy> = Box::new(NonePolicy);
+                $(policy = policy.merge(&$v.policy).unwrap();)+
+                GPolicied::make(($($v.inner,)+), policy)
+            }
+        }
+
+        impl <$($t),+> InternalizePolicy for GPolicied<($($t,)+)> {
+            type Result = ($(GPolicied<$t>,)+);
+            fn internalize_policy(self) -> Self::Result {
+                let GPolicied { inner: ($($v,)+), policy } = self;
+                ($(GPolicied::make($v, policy.clone()),)+)
+            }
+        }
+    };
+}
+
+gpolicied_tuple!(A a, B b);
+gpolicied_tuple!(A a, B b, C c);
+gpolicied_tuple!(A a, B b, C c, D d);
+
+// Maps implement InternalizePolicy now; this trait only forwards to it.
+#[allow(non_camel_case_types)]
+#[deprecated(note = "use InternalizePolicy::internalize_policy")]
+pub trait InternalizePolicy_2_1 {
+    type Result;
+    fn internalize_policy_2_1(self) -> Self::Result;
+}
+
 impl <T> Policied<T> for GPolicied<T> 
 {
     fn make(inner: T, policy: Box<dyn Policy>) -> Self {
@@ -130,14 +185,14 @@ impl <T> Policied<T> for GPolicied<T>
     fn get_policy(&self) -> &Box<dyn Policy> {
         &self.policy
     }
-    fn remove_policy(&mut self) -> () { self.policy = Box::new(NonePolicy); }
+    fn remove_policy(&mut self) { self.policy = Box::new(NonePolicy); }
     fn export_check(self, ctxt: &crate::filter::Context) -> Result<T, PolicyError> 
     {
-        self.get_policy().check(&ctxt).and_then(|_| Ok(self.inner))
+        self.get_policy().check_flow(ctxt).map(|_| self.inner)
     }
     fn export_check_borrow(&self, ctxt: &crate::filter::Context) -> Result<&T, PolicyError> 
     {
-        self.get_policy().check(&ctxt).and_then(|_| Ok(&self.inner))
+        self.get_policy().check_flow(ctxt).map(|_| &self.inner)
     }
     fn unsafe_export(self) -> T 
     {
@@ -148,36 +203,889 @@ impl <T> Policied<T> for GPolicied<T>
 pub type GPoliciedVec<T> = GPolicied<Vec<T>>;
 
 impl <T> GPoliciedVec<T> {
+    // The element's policy is merged into the vector's. Types generated by 
+    // derive_policied_vec! took a plain value here; use push_unpolicied for that, so 
+    // that a policied value is never silently stored as a nested GPolicied element.
     pub fn push(&mut self, e: GPolicied<T>) {
         let GPolicied { policy, inner } = e;
         self.policy = self.policy.merge(&policy).unwrap();
         self.inner.push(inner);
     }
 
+    // Same as push, under the name derive_policied_vec! types use.
+    pub fn push_policy(&mut self, e: GPolicied<T>) {
+        self.push(e)
+    }
+
+    // Appends a value that carries no policy of its own; it is covered by the vector's.
+    pub fn push_unpolicied(&mut self, value: T) {
+        self.inner.push(value);
+    }
+
     pub fn pop(&mut self) -> Option<GPolicied<T>> {
         self.inner.pop().map(|e| GPolicied { inner: e, policy: self.policy.clone() })
     }
+
+    pub fn sort_by<F>(&mut self, compare: F) where F: FnMut(&T, &T) -> std::cmp::Ordering {
+        self.inner.sort_by(compare)
+    }
+
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, f: F) {
+        self.inner.sort_by_key(f)
+    }
+
+    pub fn len(&self) -> GPolicied<usize> {
+        self.as_ref().map(|v| v.len())
+    }
+
+    pub fn is_empty(&self) -> GPolicied<bool> {
+        self.as_ref().map(|v| v.is_empty())
+    }
+
+    pub fn iter(&self) -> GPoliciedIter<std::slice::Iter<'_, T>> {
+        GPoliciedIter { iter: self.inner.iter(), policy: self.policy.clone() }
+    }
+
+    // Like iter, but consumes the vector and hands its elements to the adapters by value.
+    pub fn into_policied_iter(self) -> GPoliciedIter<std::vec::IntoIter<T>> {
+        GPoliciedIter { iter: self.inner.into_iter(), policy: self.policy }
+    }
 }
 
-pub type PoliciedValHashMap<K, V> = GPolicied<HashMap<K,V>>;
 
-impl <K,V> PoliciedValHashMap<K, V> 
-where
-    K : Eq + core::hash::Hash,
-{
-    pub fn insert(&mut self, k: K, v: GPolicied<V>) -> Option<GPolicied<V>> {
-        let GPolicied { policy, inner } = v;
-        let ret = self.inner.insert(k, inner).map(|r| GPolicied::make(r, self.policy.clone()));
-        self.policy = self.policy.merge(&policy).unwrap();
-        ret
+/*
+An iterator whose elements all carry one policy. Adapters work on the plain
+elements and keep the policy attached, and whatever the iterator is consumed
+into (fold, sum, collect, ...) comes back policied. It is deliberately not an
+Iterator: stepping through it with next() or a for loop would reveal the length
+and, through filter, anything the predicate looks at. To get at the elements one
+by one, collect the vector with internalize_policy, which is explicit about that.
+Usage: grades.iter().filter(|g| **g >= 50).count()
+*/
+pub struct GPoliciedIter<I> {
+    iter: I,
+    policy: Box<dyn Policy>
+}
+
+impl <I: Iterator> GPoliciedIter<I> {
+    pub fn map<B, F: FnMut(I::Item) -> B>(self, f: F) -> GPoliciedIter<std::iter::Map<I, F>> {
+        GPoliciedIter { iter: self.iter.map(f), policy: self.policy }
     }
-    pub fn get(&self, k: &K) -> Option<GPolicied<&V>> {
-        self.inner.get(k).map(|v| GPolicied::make(v, self.policy.clone()))
+
+    pub fn filter<P: FnMut(&I::Item) -> bool>(self, predicate: P) -> GPoliciedIter<std::iter::Filter<I, P>> {
+        GPoliciedIter { iter: self.iter.filter(predicate), policy: self.policy }
     }
-    pub fn insert_kv(&mut self, kv: GPolicied<(K, V)>) -> Option<GPolicied<V>> {
-        let GPolicied { policy, inner: (k, v) } = kv;
-        let ret = self.inner.insert(k, v).map(|r| GPolicied::make(r, self.policy.clone()));
-        self.policy = self.policy.merge(&policy).unwrap();
-        ret
+
+    pub fn fold<B, F: FnMut(B, I::Item) -> B>(self, init: B, f: F) -> GPolicied<B> {
+        GPolicied::make(self.iter.fold(init, f), self.policy)
+    }
+
+    pub fn sum<S: std::iter::Sum<I::Item>>(self) -> GPolicied<S> {
+        GPolicied::make(self.iter.sum(), self.policy)
+    }
+
+    pub fn count(self) -> GPolicied<usize> {
+        GPolicied::make(self.iter.count(), self.policy)
+    }
+
+    pub fn max_by<F>(self, compare: F) -> GPolicied<Option<I::Item>>
+    where
+        F: FnMut(&I::Item, &I::Item) -> std::cmp::Ordering
+    {
+        GPolicied::make(self.iter.max_by(compare), self.policy)
+    }
+
+    pub fn min_by<F>(self, compare: F) -> GPolicied<Option<I::Item>>
+    where
+        F: FnMut(&I::Item, &I::Item) -> std::cmp::Ordering
+    {
+        GPolicied::make(self.iter.min_by(compare), self.policy)
+    }
+
+    pub fn collect<C: std::iter::FromIterator<I::Item>>(self) -> GPolicied<C> {
+        GPolicied::make(self.iter.collect(), self.policy)
+    }
+}
+
+// Collecting or summing separately policied values merges their policies.
+impl <T, C: std::iter::FromIterator<T>> std::iter::FromIterator<GPolicied<T>> for GPolicied<C> {
+    fn from_iter<I: IntoIterator<Item = GPolicied<T>>>(iter: I) -> Self {
+        let mut policy: Box<dyn Policy> = Box::new(NonePolicy);
+        let inner = iter.into_iter().map(|GPolicied { inner, policy: p }| {
+            policy = policy.merge(&p).unwrap();
+            inner
+        }).collect();
+        GPolicied::make(inner, policy)
+    }
+}
+
+impl <T, S: std::iter::Sum<T>> std::iter::Sum<GPolicied<T>> for GPolicied<S> {
+    fn sum<I: Iterator<Item = GPolicied<T>>>(iter: I) -> Self {
+        let mut policy: Box<dyn Policy> = Box::new(NonePolicy);
+        let inner = iter.map(|GPolicied { inner, policy: p }| {
+            policy = policy.merge(&p).unwrap();
+            inner
+        }).sum();
+        GPolicied::make(inner, policy)
+    }
+}
+
+impl <T: Clone> GPoliciedVec<T> {
+    // Element `index`, carrying the vector's policy. Whether there is one depends on the 
+    // length, so the Option is policied too (see PoliciedElemVec::get).
+    // Usage: roster.get(0).unwrap_or(default).into_policied()
+    pub fn get(&self, index: usize) -> GPolicied<Option<T>> {
+        self.as_ref().map(|v| v.get(index).cloned())
+    }
+}
+
+/*
+A vector that keeps every element's policy separately instead of merging them
+into one, so one student's entry doesn't become unreadable because of another's.
+The container policy covers only what the vector itself reveals: its length and
+the order of its elements. Anything handed out by position carries both the
+element's and the container's policy, and get and pop return a policied Option so
+that probing an index doesn't reveal the length. Note that iterating does: the
+items carry both policies, but how many there are is visible.
+Usage: roster.filter_exportable(&ctxt)
+*/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PoliciedElemVec<T> {
+    elems: Vec<GPolicied<T>>,
+    policy: Box<dyn Policy>
+}
+
+impl <T> PoliciedElemVec<T> {
+    pub fn new() -> Self {
+        Self::with_policy(Box::new(NonePolicy))
+    }
+
+    pub fn with_policy(policy: Box<dyn Policy>) -> Self {
+        PoliciedElemVec { elems: Vec::new(), policy }
+    }
+
+    pub fn get_policy(&self) -> &Box<dyn Policy> {
+        &self.policy
+    }
+
+    // Appends without touching the container policy or any other element's.
+    pub fn push(&mut self, e: GPolicied<T>) {
+        self.elems.push(e);
+    }
+
+    pub fn pop(&mut self) -> GPolicied<Option<T>> {
+        match self.elems.pop() {
+            Some(GPolicied { inner, policy }) => GPolicied::make(Some(inner), self.policy.merge(&policy).unwrap()),
+            None => GPolicied::make(None, self.policy.clone()),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> GPolicied<Option<&T>> {
+        match self.elems.get(index) {
+            Some(e) => GPolicied::make(Some(&e.inner), self.policy.merge(&e.policy).unwrap()),
+            None => GPolicied::make(None, self.policy.clone()),
+        }
+    }
+
+    pub fn len(&self) -> GPolicied<usize> {
+        GPolicied::make(self.elems.len(), self.policy.clone())
+    }
+
+    pub fn is_empty(&self) -> GPolicied<bool> {
+        GPolicied::make(self.elems.is_empty(), self.policy.clone())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = GPolicied<&T>> {
+        self.elems.iter().map(move |e| GPolicied::make(&e.inner, self.policy.merge(&e.policy).unwrap()))
+    }
+
+    // The new order depends on the elements' values, so their policies move
+    // into the container policy; the elements themselves keep their own.
+    pub fn sort_by<F>(&mut self, mut compare: F) where F: FnMut(&T, &T) -> std::cmp::Ordering {
+        for e in &self.elems {
+            self.policy = self.policy.merge(&e.policy).unwrap();
+        }
+        self.elems.sort_by(|a, b| compare(&a.inner, &b.inner))
+    }
+
+    // The elements `ctxt` may receive, in order. Fails if the container policy
+    // forbids revealing which (and how many) elements there are.
+    pub fn filter_exportable(&self, ctxt: &crate::filter::Context) -> Result<Vec<&T>, PolicyError> {
+        self.policy.check_flow(ctxt)?;
+        Ok(self.elems.iter().filter_map(|e| e.export_check_borrow(ctxt).ok()).collect())
+    }
+}
+
+impl <T> Default for PoliciedElemVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The vector's policy stays on the container and is copied onto every element.
+impl <T> From<GPoliciedVec<T>> for PoliciedElemVec<T> {
+    fn from(v: GPoliciedVec<T>) -> Self {
+        let policy = v.policy.clone();
+        PoliciedElemVec { elems: v.internalize_policy(), policy }
+    }
+}
+
+// Collapses into a GPoliciedVec carrying every element's policy and the container's.
+impl <T> ExternalizePolicy for PoliciedElemVec<T> {
+    type Result = GPoliciedVec<T>;
+    fn externalize_policy(self) -> Self::Result {
+        let PoliciedElemVec { elems, policy } = self;
+        let mut v = GPolicied::make(Vec::new(), policy);
+        for e in elems {
+            v.push_policy(e);
+        }
+        v
+    }
+}
+
+impl <T> IntoIterator for PoliciedElemVec<T> {
+    type Item = GPolicied<T>;
+    type IntoIter = std::vec::IntoIter<GPolicied<T>>;
+    fn into_iter(self) -> Self::IntoIter {
+        let PoliciedElemVec { elems, policy } = self;
+        elems.into_iter()
+            .map(|GPolicied { inner, policy: p }| GPolicied::make(inner, policy.merge(&p).unwrap()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl <T> std::iter::FromIterator<GPolicied<T>> for PoliciedElemVec<T> {
+    fn from_iter<I: IntoIterator<Item = GPolicied<T>>>(iter: I) -> Self {
+        PoliciedElemVec { elems: iter.into_iter().collect(), policy: Box::new(NonePolicy) }
+    }
+}
+
+// Branching on a policied condition: whichever branch is taken, the result
+// carries the condition's policy, so the decision itself can't leak.
+impl GPolicied<bool> {
+    pub fn select<T>(self, if_true: T, if_false: T) -> GPolicied<T> {
+        self.map(|c| if c { if_true } else { if_false })
+    }
+
+    pub fn if_else<T, F, G>(self, if_true: F, if_false: G) -> GPolicied<T>
+    where
+        F: FnOnce() -> T,
+        G: FnOnce() -> T,
+    {
+        self.map(|c| if c { if_true() } else { if_false() })
+    }
+}
+
+impl <T> GPolicied<GPolicied<T>> {
+    // Collapses e.g. the result of branching between policied values.
+    pub fn flatten(self) -> GPolicied<T> {
+        let GPolicied { inner, policy } = self;
+        GPolicied::make(inner.inner, policy.merge(&inner.policy).unwrap())
+    }
+}
+
+impl <T: HasPolicied> GPolicied<T> {
+    // The value as its own policied type (e.g. a derived wrapper), keeping this policy.
+    pub fn into_policied(self) -> T::Policied {
+        T::Policied::make(self.inner, self.policy)
+    }
+}
+
+impl <T> GPolicied<Option<T>> {
+    pub fn make_option(ops: Option<GPolicied<T>>) -> Self {
+        ops.externalize_policy()
+    }
+
+    pub fn get_option(self) -> Option<GPolicied<T>> {
+        let GPolicied { inner, policy } = self;
+        inner.map(|v| GPolicied::make(v, policy))
+    }
+
+    pub fn is_some(&self) -> GPolicied<bool> {
+        self.as_ref().map(|o| o.is_some())
+    }
+
+    pub fn is_none(&self) -> GPolicied<bool> {
+        self.as_ref().map(|o| o.is_none())
+    }
+
+    pub fn and_then<U, F: FnOnce(T) -> Option<U>>(self, f: F) -> GPolicied<Option<U>> {
+        self.map(|o| o.and_then(f))
+    }
+
+    pub fn filter<P: FnOnce(&T) -> bool>(self, predicate: P) -> GPolicied<Option<T>> {
+        self.map(|o| o.filter(predicate))
+    }
+
+    // Which of the two is returned depends on this option, so the result carries both policies.
+    pub fn or(self, other: GPolicied<Option<T>>) -> GPolicied<Option<T>> {
+        let policy = self.policy.merge(&other.policy).unwrap();
+        GPolicied::make(self.inner.or(other.inner), policy)
+    }
+
+    pub fn zip<U>(self, other: GPolicied<Option<U>>) -> GPolicied<Option<(T, U)>> {
+        let policy = self.policy.merge(&other.policy).unwrap();
+        GPolicied::make(self.inner.zip(other.inner), policy)
+    }
+
+    pub fn unwrap_or(self, default: T) -> GPolicied<T> {
+        self.map(|o| o.unwrap_or(default))
+    }
+
+    pub fn unwrap_or_else<F: FnOnce() -> T>(self, f: F) -> GPolicied<T> {
+        self.map(|o| o.unwrap_or_else(f))
+    }
+
+    pub fn unwrap_or_default(self) -> GPolicied<T> where T: Default {
+        self.map(|o| o.unwrap_or_default())
+    }
+
+    pub fn ok_or<E>(self, err: E) -> GPolicied<Result<T, E>> {
+        self.map(|o| o.ok_or(err))
+    }
+
+    pub fn ok_or_else<E, F: FnOnce() -> E>(self, err: F) -> GPolicied<Result<T, E>> {
+        self.map(|o| o.ok_or_else(err))
+    }
+}
+
+impl <T, E> GPolicied<Result<T, E>> {
+    pub fn make_result(res: Result<GPolicied<T>, GPolicied<E>>) -> Self {
+        res.externalize_policy()
+    }
+
+    pub fn get_result(self) -> Result<GPolicied<T>, GPolicied<E>> {
+        self.internalize_policy()
+    }
+
+    pub fn is_ok(&self) -> GPolicied<bool> {
+        self.as_ref().map(|r| r.is_ok())
+    }
+
+    pub fn is_err(&self) -> GPolicied<bool> {
+        self.as_ref().map(|r| r.is_err())
+    }
+
+    pub fn ok(self) -> GPolicied<Option<T>> {
+        self.map(|r| r.ok())
+    }
+
+    pub fn err(self) -> GPolicied<Option<E>> {
+        self.map(|r| r.err())
+    }
+
+    pub fn and_then<U, F: FnOnce(T) -> Result<U, E>>(self, f: F) -> GPolicied<Result<U, E>> {
+        self.map(|r| r.and_then(f))
+    }
+
+    pub fn map_err<F2, O: FnOnce(E) -> F2>(self, op: O) -> GPolicied<Result<T, F2>> {
+        self.map(|r| r.map_err(op))
+    }
+
+    pub fn unwrap_or(self, default: T) -> GPolicied<T> {
+        self.map(|r| r.unwrap_or(default))
+    }
+
+    pub fn unwrap_or_else<F: FnOnce(E) -> T>(self, op: F) -> GPolicied<T> {
+        self.map(|r| r.unwrap_or_else(op))
+    }
+
+    pub fn unwrap_or_default(self) -> GPolicied<T> where T: Default {
+        self.map(|r| r.unwrap_or_default())
+    }
+}
+
+impl GPolicied<String> {
+    pub fn push_str(&mut self, string: &str) {
+        self.inner.push_str(string)
+    }
+
+    pub fn push_policy_str(&mut self, policy_string: &GPolicied<String>) 
+    -> Result<(), PolicyError> {
+        let p = self.policy.merge(&policy_string.policy)?;
+        self.inner.push_str(&policy_string.inner);
+        self.policy = p;
+        Ok(())
+    }
+
+    pub fn len(&self) -> GPolicied<usize> {
+        self.as_ref().map(|s| s.len())
+    }
+
+    pub fn is_empty(&self) -> GPolicied<bool> {
+        self.as_ref().map(|s| s.is_empty())
+    }
+
+    pub fn contains(&self, pat: &str) -> GPolicied<bool> {
+        self.as_ref().map(|s| s.contains(pat))
+    }
+
+    pub fn starts_with(&self, pat: &str) -> GPolicied<bool> {
+        self.as_ref().map(|s| s.starts_with(pat))
+    }
+
+    pub fn ends_with(&self, pat: &str) -> GPolicied<bool> {
+        self.as_ref().map(|s| s.ends_with(pat))
+    }
+
+    pub fn trim(&self) -> GPolicied<&str> {
+        self.as_ref().map(|s| s.trim())
+    }
+
+    // Panics on the same out-of-bounds or non-char-boundary ranges as `&s[range]`.
+    pub fn slice<R: std::slice::SliceIndex<str, Output = str>>(&self, range: R) -> GPolicied<&str> {
+        self.as_ref().map(|s| &s[range])
+    }
+
+    // Every piece carries the policy of the whole string.
+    pub fn split<'a>(&'a self, pat: &str) -> GPoliciedVec<&'a str> {
+        self.as_ref().map(|s| s.split(pat).collect())
+    }
+
+    pub fn to_uppercase(&self) -> GPolicied<String> {
+        self.as_ref().map(|s| s.to_uppercase())
+    }
+
+    pub fn to_lowercase(&self) -> GPolicied<String> {
+        self.as_ref().map(|s| s.to_lowercase())
+    }
+
+    pub fn replace(&self, from: &str, to: &str) -> GPolicied<String> {
+        self.as_ref().map(|s| s.replace(from, to))
+    }
+
+    // Joins policied strings; the result carries all of their policies.
+    pub fn concat<S: AsRef<str>>(parts: &[GPolicied<S>]) -> GPolicied<String> {
+        Self::join(parts, "")
+    }
+
+    pub fn join<S: AsRef<str>>(parts: &[GPolicied<S>], sep: &str) -> GPolicied<String> {
+        let mut policy: Box<dyn Policy> = Box::new(NonePolicy);
+        let mut joined = String::new();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                joined.push_str(sep);
+            }
+            joined.push_str(part.inner.as_ref());
+            policy = policy.merge(&part.policy).unwrap();
+        }
+        GPolicied::make(joined, policy)
+    }
+}
+
+/*
+An argument to policied_format!. Policied values contribute their policy to the
+formatted string; plain values are formatted as is.
+*/
+pub trait FormatArg {
+    type Value: ?Sized;
+    fn format_value(&self) -> &Self::Value;
+    fn format_policy(&self) -> Option<&Box<dyn Policy>>;
+
+    // Merges this argument's policy into `policy` and hands back the value to format.
+    fn record(&self, policy: &mut Box<dyn Policy>) -> &Self::Value {
+        if let Some(p) = self.format_policy() {
+            *policy = policy.merge(p).unwrap();
+        }
+        self.format_value()
+    }
+}
+
+impl <T> FormatArg for GPolicied<T> {
+    type Value = T;
+    fn format_value(&self) -> &T {
+        &self.inner
+    }
+    fn format_policy(&self) -> Option<&Box<dyn Policy>> {
+        Some(&self.policy)
+    }
+}
+
+impl <T: FormatArg + ?Sized> FormatArg for &T {
+    type Value = T::Value;
+    fn format_value(&self) -> &T::Value {
+        (**self).format_value()
     }
+    fn format_policy(&self) -> Option<&Box<dyn Policy>> {
+        (**self).format_policy()
+    }
+}
+
+macro_rules! plain_format_arg {
+    ($($t:ty),*) => {
+        $(impl FormatArg for $t {
+            type Value = $t;
+            fn format_value(&self) -> &$t {
+                self
+            }
+            fn format_policy(&self) -> Option<&Box<dyn Policy>> {
+                None
+            }
+        })*
+    };
 }
+
+plain_format_arg!(str, String, bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/*
+format! producing a PoliciedString that carries the merged policies of all
+policied arguments. Arguments are positional; plain values may be mixed in.
+Usage: policied_format!("{} received {}", student_id, "an A")
+*/
+#[macro_export]
+macro_rules! policied_format {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let mut policy: Box<dyn $crate::policy::Policy> = Box::new($crate::policy::NonePolicy);
+        let formatted = format!($fmt $(, $crate::generic_policied::FormatArg::record(&$arg, &mut policy))*);
+        <$crate::generic_policied::GPolicied<String> as $crate::policy::Policied<String>>::make(formatted, policy)
+    }};
+}
+
+// Operators compute on the inner values; the result carries both operands' policies.
+macro_rules! gpolicied_binop {
+    ($($tr:ident $m:ident $atr:ident $am:ident),*) => {
+        $(impl <T: std::ops::$tr<U>, U> std::ops::$tr<GPolicied<U>> for GPolicied<T> {
+            type Output = GPolicied<T::Output>;
+            fn $m(self, rhs: GPolicied<U>) -> Self::Output {
+                let GPolicied { inner, policy } = self;
+                GPolicied::make(std::ops::$tr::$m(inner, rhs.inner), policy.merge(&rhs.policy).unwrap())
+            }
+        }
+
+        impl <'a, 'b, T, U> std::ops::$tr<&'b GPolicied<U>> for &'a GPolicied<T>
+        where
+            &'a T: std::ops::$tr<&'b U>
+        {
+            type Output = GPolicied<<&'a T as std::ops::$tr<&'b U>>::Output>;
+            fn $m(self, rhs: &'b GPolicied<U>) -> Self::Output {
+                GPolicied::make(std::ops::$tr::$m(&self.inner, &rhs.inner), self.policy.merge(&rhs.policy).unwrap())
+            }
+        }
+
+        impl <T: std::ops::$atr<U>, U> std::ops::$atr<GPolicied<U>> for GPolicied<T> {
+            fn $am(&mut self, rhs: GPolicied<U>) {
+                self.policy = self.policy.merge(&rhs.policy).unwrap();
+                std::ops::$atr::$am(&mut self.inner, rhs.inner);
+            }
+        })*
+    };
+}
+
+gpolicied_binop!(
+    Add add AddAssign add_assign,
+    Sub sub SubAssign sub_assign,
+    Mul mul MulAssign mul_assign,
+    Div div DivAssign div_assign,
+    Rem rem RemAssign rem_assign,
+    BitAnd bitand BitAndAssign bitand_assign,
+    BitOr bitor BitOrAssign bitor_assign,
+    BitXor bitxor BitXorAssign bitxor_assign,
+    Shl shl ShlAssign shl_assign,
+    Shr shr ShrAssign shr_assign
+);
+
+macro_rules! gpolicied_unop {
+    ($($tr:ident $m:ident),*) => {
+        $(impl <T: std::ops::$tr> std::ops::$tr for GPolicied<T> {
+            type Output = GPolicied<T::Output>;
+            fn $m(self) -> Self::Output {
+                self.map(std::ops::$tr::$m)
+            }
+        })*
+    };
+}
+
+gpolicied_unop!(Neg neg, Not not);
+
+// Comparisons return a policied bool so the outcome can't be branched on untainted.
+impl <T> GPolicied<T> {
+    fn combine<U, V, F: FnOnce(&T, &U) -> V>(&self, other: &GPolicied<U>, f: F) -> GPolicied<V> {
+        GPolicied::make(f(&self.inner, &other.inner), self.policy.merge(&other.policy).unwrap())
+    }
+
+    pub fn eq<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialEq<U> {
+        self.combine(other, |a, b| a == b)
+    }
+
+    pub fn ne<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialEq<U> {
+        self.combine(other, |a, b| a != b)
+    }
+
+    pub fn lt<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialOrd<U> {
+        self.combine(other, |a, b| a < b)
+    }
+
+    pub fn le<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialOrd<U> {
+        self.combine(other, |a, b| a <= b)
+    }
+
+    pub fn gt<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialOrd<U> {
+        self.combine(other, |a, b| a > b)
+    }
+
+    pub fn ge<U>(&self, other: &GPolicied<U>) -> GPolicied<bool> where T: PartialOrd<U> {
+        self.combine(other, |a, b| a >= b)
+    }
+
+    pub fn compare(&self, other: &GPolicied<T>) -> GPolicied<std::cmp::Ordering> where T: Ord {
+        self.combine(other, Ord::cmp)
+    }
+}
+
+// Policied types without a dedicated wrapper are GPolicied.
+macro_rules! gpolicied_has_policied {
+    ($($t:ty),*) => {
+        $(impl HasPolicied for $t {
+            type Policied = GPolicied<$t>;
+        })*
+    };
+}
+
+gpolicied_has_policied!(String, bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl <T> HasPolicied for Vec<T> {
+    type Policied = GPolicied<Vec<T>>;
+}
+
+impl <T> HasPolicied for Option<T> {
+    type Policied = GPolicied<Option<T>>;
+}
+
+/*
+Maps under a single policy. Inserting a value merges its policy into the map's;
+everything read back out (values, lookups, iteration, the length) carries the
+map's policy. Lookups return a policied Option, so whether a key is present is
+covered too.
+*/
+pub type PoliciedValHashMap<K, V> = GPolicied<HashMap<K,V>>;
+
+pub type PoliciedValBTreeMap<K, V> = GPolicied<BTreeMap<K,V>>;
+
+// An entry of a single-policy map, see PoliciedValHashMap::entry.
+pub struct PoliciedEntry<'a, E> {
+    entry: E,
+    policy: &'a mut Box<dyn Policy>
+}
+
+macro_rules! gpolicied_map {
+    ($map:ident, $map_mod:ident, $($key_bound:tt)+) => {
+        impl <K: $($key_bound)+, V> GPolicied<$map<K, V>> {
+            // The replaced value, if any, under the map's policy from before the insert.
+            pub fn insert(&mut self, k: K, v: GPolicied<V>) -> GPolicied<Option<V>> {
+                let GPolicied { policy, inner } = v;
+                let ret = GPolicied::make(self.inner.insert(k, inner), self.policy.clone());
+                self.policy = self.policy.merge(&policy).unwrap();
+                ret
+            }
+            pub fn get(&self, k: &K) -> GPolicied<Option<&V>> {
+                self.as_ref().map(|m| m.get(k))
+            }
+            pub fn insert_kv(&mut self, kv: GPolicied<(K, V)>) -> GPolicied<Option<V>> {
+                let GPolicied { policy, inner: (k, v) } = kv;
+                let ret = GPolicied::make(self.inner.insert(k, v), self.policy.clone());
+                self.policy = self.policy.merge(&policy).unwrap();
+                ret
+            }
+            pub fn remove(&mut self, k: &K) -> GPolicied<Option<V>> {
+                GPolicied::make(self.inner.remove(k), self.policy.clone())
+            }
+            pub fn contains_key(&self, k: &K) -> GPolicied<bool> {
+                self.as_ref().map(|m| m.contains_key(k))
+            }
+            pub fn len(&self) -> GPolicied<usize> {
+                self.as_ref().map(|m| m.len())
+            }
+            pub fn is_empty(&self) -> GPolicied<bool> {
+                self.as_ref().map(|m| m.is_empty())
+            }
+            pub fn iter(&self) -> GPoliciedIter<std::collections::$map_mod::Iter<'_, K, V>> {
+                GPoliciedIter { iter: self.inner.iter(), policy: self.policy.clone() }
+            }
+            pub fn keys(&self) -> GPoliciedIter<std::collections::$map_mod::Keys<'_, K, V>> {
+                GPoliciedIter { iter: self.inner.keys(), policy: self.policy.clone() }
+            }
+            pub fn values(&self) -> GPoliciedIter<std::collections::$map_mod::Values<'_, K, V>> {
+                GPoliciedIter { iter: self.inner.values(), policy: self.policy.clone() }
+            }
+            pub fn into_policied_iter(self) -> GPoliciedIter<std::collections::$map_mod::IntoIter<K, V>> {
+                GPoliciedIter { iter: self.inner.into_iter(), policy: self.policy }
+            }
+            pub fn entry(&mut self, key: K) -> PoliciedEntry<'_, std::collections::$map_mod::Entry<'_, K, V>> {
+                PoliciedEntry { entry: self.inner.entry(key), policy: &mut self.policy }
+            }
+        }
+
+        impl <'a, K: $($key_bound)+, V> PoliciedEntry<'a, std::collections::$map_mod::Entry<'a, K, V>> {
+            pub fn key(&self) -> &K {
+                self.entry.key()
+            }
+
+            pub fn or_insert(self, default: GPolicied<V>) -> GPolicied<&'a mut V> {
+                self.or_insert_with(|| default)
+            }
+
+            // A default that gets inserted merges its policy into the map's.
+            pub fn or_insert_with<F: FnOnce() -> GPolicied<V>>(self, default: F) -> GPolicied<&'a mut V> {
+                use std::collections::$map_mod::Entry;
+                let PoliciedEntry { entry, policy } = self;
+                let value = match entry {
+                    Entry::Occupied(o) => o.into_mut(),
+                    Entry::Vacant(v) => {
+                        let GPolicied { inner, policy: p } = default();
+                        *policy = policy.merge(&p).unwrap();
+                        v.insert(inner)
+                    }
+                };
+                GPolicied::make(value, policy.clone())
+            }
+
+            pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+                let PoliciedEntry { entry, policy } = self;
+                PoliciedEntry { entry: entry.and_modify(f), policy }
+            }
+        }
+
+        impl <K: $($key_bound)+, V> ExternalizePolicy for $map<K, GPolicied<V>> {
+            type Result = GPolicied<$map<K, V>>;
+            fn externalize_policy(self) -> Self::Result {
+                let mut m = GPolicied::make_default($map::new());
+                for (k, v) in self {
+                    m.insert(k, v);
+                }
+                m
+            }
+        }
+
+        impl <K: $($key_bound)+, V> InternalizePolicy for GPolicied<$map<K, V>> {
+            type Result = $map<K, GPolicied<V>>;
+            fn internalize_policy(self) -> Self::Result {
+                let (inner, policy) = self.unsafe_decompose();
+                inner.into_iter().map(|(k, v)| (k, v.policied_with(policy.clone()))).collect()
+            }
+        }
+
+        #[allow(deprecated)]
+        impl <K: $($key_bound)+, V> InternalizePolicy_2_1 for GPolicied<$map<K, V>> {
+            type Result = $map<K, GPolicied<V>>;
+            fn internalize_policy_2_1(self) -> Self::Result {
+                self.internalize_policy()
+            }
+        }
+
+    };
+}
+
+gpolicied_map!(HashMap, hash_map, Eq + std::hash::Hash);
+gpolicied_map!(BTreeMap, btree_map, Ord);
+
+/*
+Maps that keep a policy per entry, like PoliciedElemVec. An entry's policy covers 
+its value and, for policied keys, its key. The container policy covers which keys 
+are present (lookups, iteration, the length); inserting a policied key therefore 
+also merges the key's policy into it. get, insert and remove return a policied 
+Option, which carries the container policy whether or not the key was there.
+Usage: grades.insert_policied_key(student_id, grade)
+*/
+macro_rules! policied_elem_map {
+    ($name:ident, $map:ident, $($key_bound:tt)+) => {
+        #[derive(Serialize, Deserialize, Clone)]
+        pub struct $name<K: $($key_bound)+, V> {
+            entries: $map<K, GPolicied<V>>,
+            policy: Box<dyn Policy>
+        }
+
+        impl <K: $($key_bound)+, V> $name<K, V> {
+            pub fn new() -> Self {
+                Self::with_policy(Box::new(NonePolicy))
+            }
+
+            pub fn with_policy(policy: Box<dyn Policy>) -> Self {
+                $name { entries: $map::new(), policy }
+            }
+
+            pub fn get_policy(&self) -> &Box<dyn Policy> {
+                &self.policy
+            }
+
+            pub fn insert(&mut self, k: K, v: GPolicied<V>) -> GPolicied<Option<V>> {
+                let old = self.entries.insert(k, v);
+                self.with_container_policy(old)
+            }
+
+            pub fn insert_policied_key(&mut self, k: GPolicied<K>, v: GPolicied<V>) -> GPolicied<Option<V>> {
+                let GPolicied { inner: key, policy: key_policy } = k;
+                self.policy = self.policy.merge(&key_policy).unwrap();
+                let GPolicied { inner, policy } = v;
+                self.insert(key, GPolicied::make(inner, policy.merge(&key_policy).unwrap()))
+            }
+
+            pub fn get(&self, k: &K) -> GPolicied<Option<&V>> {
+                match self.entries.get(k) {
+                    Some(e) => GPolicied::make(Some(&e.inner), self.policy.merge(&e.policy).unwrap()),
+                    None => GPolicied::make(None, self.policy.clone()),
+                }
+            }
+
+            pub fn remove(&mut self, k: &K) -> GPolicied<Option<V>> {
+                let old = self.entries.remove(k);
+                self.with_container_policy(old)
+            }
+
+            // An entry taken out of the map, under its own and the container's policy.
+            fn with_container_policy(&self, entry: Option<GPolicied<V>>) -> GPolicied<Option<V>> {
+                match entry {
+                    Some(GPolicied { inner, policy }) => GPolicied::make(Some(inner), self.policy.merge(&policy).unwrap()),
+                    None => GPolicied::make(None, self.policy.clone()),
+                }
+            }
+
+            pub fn contains_key(&self, k: &K) -> GPolicied<bool> {
+                GPolicied::make(self.entries.contains_key(k), self.policy.clone())
+            }
+
+            pub fn len(&self) -> GPolicied<usize> {
+                GPolicied::make(self.entries.len(), self.policy.clone())
+            }
+
+            pub fn is_empty(&self) -> GPolicied<bool> {
+                GPolicied::make(self.entries.is_empty(), self.policy.clone())
+            }
+
+            pub fn iter(&self) -> impl Iterator<Item = GPolicied<(&K, &V)>> {
+                self.entries.iter().map(move |(k, e)| GPolicied::make((k, &e.inner), self.policy.merge(&e.policy).unwrap()))
+            }
+
+            // The entries `ctxt` may receive. Fails if the container policy forbids
+            // revealing which keys are present.
+            pub fn filter_exportable(&self, ctxt: &crate::filter::Context) -> Result<Vec<(&K, &V)>, PolicyError> {
+                self.policy.check_flow(ctxt)?;
+                Ok(self.entries.iter()
+                    .filter_map(|(k, e)| e.export_check_borrow(ctxt).ok().map(|v| (k, v)))
+                    .collect())
+            }
+        }
+
+        impl <K: $($key_bound)+, V> Default for $name<K, V> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        // Collapses into a single-policy map carrying every entry's policy and the container's.
+        impl <K: $($key_bound)+, V> ExternalizePolicy for $name<K, V> {
+            type Result = GPolicied<$map<K, V>>;
+            fn externalize_policy(self) -> Self::Result {
+                let $name { entries, policy } = self;
+                let mut m = GPolicied::make($map::new(), policy);
+                for (k, v) in entries {
+                    m.insert(k, v);
+                }
+                m
+            }
+        }
+    };
+}
+
+policied_elem_map!(PoliciedElemHashMap, HashMap, Eq + std::hash::Hash);
+policied_elem_map!(PoliciedElemBTreeMap, BTreeMap, Ord);
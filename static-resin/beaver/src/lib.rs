@@ -1,3 +1,6 @@
+// `Policy` trait objects are passed around as `&Box<dyn Policy>` throughout the API.
+#![allow(clippy::borrowed_box)]
+
 #[macro_use]
 extern crate serde_derive;
 
@@ -5,4 +8,7 @@ pub mod policy;
 pub mod filter;
 pub mod beaverio;
 pub mod macros;
-pub mod generic_policied;
\ No newline at end of file
+pub mod generic_policied;
+pub mod channel;
+#[cfg(feature = "tokio")]
+pub mod async_beaverio;
\ No newline at end of file
@@ -1,7 +1,7 @@
 #[macro_export]
 macro_rules! derive_policied {
     ($input_type:ty, $output_type_name:ident) => {
-        derive_policied!($input_type, $output_type_name ,);
+        $crate::derive_policied!($input_type, $output_type_name ,);
     };
     ($input_type:ty, $output_type_name:ident, $($ty_vars:ident),*) => {
         #[derive(Serialize, Deserialize, Clone)]
@@ -21,7 +21,7 @@ macro_rules! derive_policied {
             }
             fn remove_policy(&mut self) -> () { self.policy = Box::new($crate::policy::NonePolicy); }
             fn export_check(self, ctxt: &$crate::filter::Context) -> Result<$input_type, $crate::policy::PolicyError> {
-                match self.get_policy().check(&ctxt) {
+                match self.get_policy().check_flow(&ctxt) {
                     Ok(_) => {
                         Ok(self.inner)
                     }, 
@@ -29,7 +29,7 @@ macro_rules! derive_policied {
                 }
             }    
             fn export_check_borrow(&self, ctxt: &$crate::filter::Context) -> Result<&$input_type, $crate::policy::PolicyError> {
-                match self.get_policy().check(&ctxt) {
+                match self.get_policy().check_flow(&ctxt) {
                     Ok(_) => {
                         Ok(&self.inner)
                     }, 
@@ -40,14 +40,31 @@ macro_rules! derive_policied {
                 self.inner
             }
         }
+
+        impl <$($ty_vars),*> From<$output_type_name<$($ty_vars),*>> for $crate::generic_policied::GPolicied<$input_type> {
+            fn from(p: $output_type_name<$($ty_vars),*>) -> Self {
+                <$crate::generic_policied::GPolicied<$input_type> as $crate::policy::Policied<$input_type>>::make(p.inner, p.policy)
+            }
+        }
+
+        impl <$($ty_vars),*> From<$crate::generic_policied::GPolicied<$input_type>> for $output_type_name<$($ty_vars),*> {
+            fn from(p: $crate::generic_policied::GPolicied<$input_type>) -> Self {
+                let (inner, policy) = p.unsafe_decompose();
+                Self { inner, policy }
+            }
+        }
     };
 }
 
 /*
 This macro can be used to easily create a simple policied version of a type. 
-It creates a PoliciedTYPE struct and implements the Policied<TYPE> trait for it.
+It creates a PoliciedTYPE struct and implements the Policied<TYPE> trait for it, 
+along with From conversions to and from GPolicied<TYPE>.
 
 Usage: derive_policied!(TYPE, PoliciedTYPE);
+
+Note: GPolicied<TYPE> (possibly behind a type alias) is the preferred way to get a 
+policied version of a type; the library itself no longer uses this macro.
 */
 
 /*
@@ -84,27 +101,51 @@ macro_rules! derive_policied_vec {
             pub fn sort_by<F>(&mut self, compare: F) where F: FnMut(&$unpolicied_element_type, &$unpolicied_element_type) -> std::cmp::Ordering, {
                 self.inner.sort_by(compare)
             }
+
+            pub fn sort_by_key<K: Ord, F>(&mut self, f: F) where F: FnMut(&$unpolicied_element_type) -> K, {
+                self.inner.sort_by_key(f)
+            }
         }
     }
 }
 
+/*
+This macro can be used to easily create a policied hash map whose values are of a 
+policied type, where the policy on the map is a merged version of the policies on 
+its values. Values taken out of the map carry the map's policy.
+It creates a PoliciedTYPEMap struct and implements various map operations.
+PoliciedTYPE must have been previously derived (or be a GPolicied alias).
+
+Usage: derive_policied_map!(PoliciedTYPEMap, KEY, TYPE, PoliciedTYPE);
+*/
 #[macro_export]
 macro_rules! derive_policied_map {
-    ($policied_map_type:ident, $key_type:ty, $unpolicied_element_type:ty, $policied_element_type:ident) => {
+    ($policied_map_type:ident, $key_type:ty, $unpolicied_element_type:ty, $policied_element_type:ty) => {
         $crate::derive_policied!(std::collections::HashMap<$key_type,$unpolicied_element_type>, $policied_map_type);
 
         impl $policied_map_type {
+            pub fn new() -> Self {
+                Self { policy: Box::new($crate::policy::NonePolicy), inner: std::collections::HashMap::new() }
+            }
+
             pub fn insert(&mut self, key: $key_type, v: $policied_element_type) -> Option<$policied_element_type> {
-                self.policy = self.policy.merge(v.get_policy()).unwrap();
-                self.inner.insert(key, v.export());
+                self.policy = self.policy.merge($crate::policy::Policied::get_policy(&v)).unwrap();
+                let old = self.inner.insert(key, $crate::policy::Policied::unsafe_export(v));
+                old.map(|o| <$policied_element_type as $crate::policy::Policied<$unpolicied_element_type>>::make(o, self.policy.clone()))
             }
 
-            pub fn get(&mut self, key: &$key_type, v: $policied_element_type) -> Option<&$policied_element_type> {
-                self.inner.get(key).map(|v| $policied_element_type { inner: v, policy: self.policy.clone()})
+            pub fn get(&self, key: &$key_type) -> Option<$policied_element_type> {
+                self.inner.get(key).map(|v| <$policied_element_type as $crate::policy::Policied<$unpolicied_element_type>>::make(v.clone(), self.policy.clone()))
             }
 
-            pub fn new() -> Self {
-                Self { policy: $crate::policy::NonePolicy, inner: std::collections::HashMap::new() }
+            pub fn remove(&mut self, key: &$key_type) -> Option<$policied_element_type> {
+                self.inner.remove(key).map(|v| <$policied_element_type as $crate::policy::Policied<$unpolicied_element_type>>::make(v, self.policy.clone()))
+            }
+        }
+
+        impl Default for $policied_map_type {
+            fn default() -> Self {
+                Self::new()
             }
         }
     }
@@ -1,12 +1,11 @@
 use std::fmt;
 use crate::filter;
 use std::error;
+use std::sync::Arc;
 use dyn_clone::DynClone;
 
 extern crate beaver_derive;
-use crate::derive_policied;
-use crate::derive_policied_vec;
-use crate::derive_policied_option;
+use crate::generic_policied::{GPolicied, GPoliciedVec, PoliciedElemVec};
 
 
 extern crate serde;
@@ -14,10 +13,23 @@ extern crate erased_serde;
 extern crate typetag;
 
 // ------------------- MAIN POLICY TRAITS/STRUCTS ----------------------------------
+// Policies are Send + Sync so that policied values can cross threads and live in 
+// shared state (Arc<Mutex<...>>) like the data they protect.
 #[typetag::serde(tag = "type")]
-pub trait Policy : DynClone + erased_serde::Serialize {
+pub trait Policy : DynClone + erased_serde::Serialize + Send + Sync {
     fn check(&self, ctxt: &filter::Context) -> Result<(), PolicyError>; 
     fn merge(&self, _other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError>;
+
+    // Entry point used by the library when data leaves: composite contexts are split 
+    // into their hops and `check` must pass for each of them. A flow without hops 
+    // has no sink to check and is denied.
+    fn check_flow(&self, ctxt: &filter::Context) -> Result<(), PolicyError> {
+        let hops = ctxt.hops();
+        if hops.is_empty() {
+            return Err(PolicyError { message: "Flow has no hops".to_string() });
+        }
+        hops.into_iter().try_for_each(|hop| self.check(hop))
+    }
 }
 
 dyn_clone::clone_trait_object!(Policy);
@@ -31,6 +43,37 @@ pub trait Policied<T> //: erased_serde::Serialize
     fn export_check_borrow(&self, ctxt: &filter::Context) -> Result<&T, PolicyError>;
 }
 
+// Boxed policied values are policied too, so they can be passed wherever a Policied<T> is expected.
+impl<T, P: Policied<T>> Policied<T> for Box<P> {
+    fn make(inner: T, policy: Box<dyn Policy>) -> Self {
+        Box::new(P::make(inner, policy))
+    }
+    fn get_policy(&self) -> &Box<dyn Policy> {
+        (**self).get_policy()
+    }
+    fn remove_policy(&mut self) {
+        (**self).remove_policy()
+    }
+    fn unsafe_export(self) -> T {
+        (*self).unsafe_export()
+    }
+    fn export_check(self, ctxt: &filter::Context) -> Result<T, PolicyError> {
+        (*self).export_check(ctxt)
+    }
+    fn export_check_borrow(&self, ctxt: &filter::Context) -> Result<&T, PolicyError> {
+        (**self).export_check_borrow(ctxt)
+    }
+}
+
+/*
+Links a type to the policied type that wraps it, e.g. String to PoliciedString. 
+#[derive(Policied)] implements it for the types it is used on, which lets a bare 
+#[policy_protected] field find the wrapper of a nested policied struct on its own.
+*/
+pub trait HasPolicied: Sized {
+    type Policied: Policied<Self>;
+}
+
 #[derive(Debug, Clone)]
 pub struct PolicyError { pub message: String }
 
@@ -46,6 +89,50 @@ impl error::Error for PolicyError {
     }
 }
 
+/*
+A policy field that #[derive(Policy)] can compare context values against. Optional 
+and list-valued fields match if any of their values does.
+*/
+pub trait PolicyValue {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool;
+}
+
+impl PolicyValue for str {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        pred(self)
+    }
+}
+
+impl PolicyValue for String {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        pred(self)
+    }
+}
+
+impl PolicyValue for std::net::IpAddr {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        pred(&self.to_string())
+    }
+}
+
+impl<T: PolicyValue + ?Sized> PolicyValue for &T {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        (**self).any_matches(pred)
+    }
+}
+
+impl<T: PolicyValue> PolicyValue for Option<T> {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        self.as_ref().is_some_and(|v| v.any_matches(pred))
+    }
+}
+
+impl<T: PolicyValue> PolicyValue for Vec<T> {
+    fn any_matches(&self, pred: &dyn Fn(&str) -> bool) -> bool {
+        self.iter().any(|v| v.any_matches(pred))
+    }
+}
+
 // ------------------- LIBRARY POLICY STRUCTS --------------------------------------
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -63,15 +150,16 @@ impl Policy for NonePolicy {
 }
 
 // Possile exploration: could store a vector of policies
+// The two halves are shared, so cloning (and merging again) doesn't copy the tree.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct MergePolicy {
-    policy1: Box<dyn Policy>,
-    policy2: Box<dyn Policy>,
+    policy1: Arc<dyn Policy>,
+    policy2: Arc<dyn Policy>,
 }
 
 impl MergePolicy {
     pub fn make(policy1: Box<dyn Policy>, policy2: Box<dyn Policy>) -> MergePolicy {
-        MergePolicy { policy1, policy2 }
+        MergePolicy { policy1: Arc::from(policy1), policy2: Arc::from(policy2) }
     }
 }
 
@@ -82,37 +170,87 @@ impl Policy for MergePolicy {
     }
 
     fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
-        Ok(Box::new(MergePolicy { 
-            policy1: Box::new(self.clone()),
-            policy2: other.clone(),
-        }))
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
     }
 }
 
-// ------------------- LIBRARY POLICIED STRUCTS --------------------------------------
+/*
+Wraps a policy behind an Arc so that cloning it, and with it every policied value 
+carrying it, is a reference count bump rather than a copy of the policy. Checks and 
+merges are delegated to the wrapped policy.
+Usage: GPolicied::make(data, SharedPolicy::make(policy)), or value.share_policy()
+*/
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SharedPolicy {
+    inner: Arc<dyn Policy>,
+}
+
+impl SharedPolicy {
+    pub fn make(policy: Box<dyn Policy>) -> Box<dyn Policy> {
+        Box::new(SharedPolicy { inner: Arc::from(policy) })
+    }
+}
+
+#[typetag::serde]
+impl Policy for SharedPolicy {
+    fn check(&self, ctxt: &filter::Context) -> Result<(), PolicyError> {
+        self.inner.check(ctxt)
+    }
+
+    fn check_flow(&self, ctxt: &filter::Context) -> Result<(), PolicyError> {
+        self.inner.check_flow(ctxt)
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        self.inner.merge(other)
+    }
+}
 
-derive_policied!(String, PoliciedString);
-impl PoliciedString {
-    pub fn push_str(&mut self, string: &str) {
-        self.inner.push_str(string)
-    }
-
-    pub fn push_policy_str(&mut self, policy_string: &PoliciedString) 
-    -> Result<(), PolicyError> {
-        match (*(self.policy)).merge(&(policy_string.policy)) {
-            Ok(p) => {
-                self.inner.push_str(&policy_string.inner);
-                self.policy = p;
-                return Ok(());
-            },
-            Err(pe) => { Err(pe) }
+/*
+Only allows flows into contexts tagged with one of the listed purposes 
+(see filter::Context::with_purpose). Untagged contexts are rejected.
+*/
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PurposePolicy {
+    allowed_purposes: Vec<String>,
+}
+
+impl PurposePolicy {
+    pub fn make(allowed_purposes: Vec<String>) -> PurposePolicy {
+        PurposePolicy { allowed_purposes }
+    }
+}
+
+#[typetag::serde]
+impl Policy for PurposePolicy {
+    fn check(&self, ctxt: &filter::Context) -> Result<(), PolicyError> {
+        match ctxt.purpose() {
+            Some(p) if self.allowed_purposes.iter().any(|a| a == p) => Ok(()),
+            Some(p) => Err(PolicyError { message: format!("Purpose \"{}\" is not allowed", p) }),
+            None => Err(PolicyError { message: "Flow must declare a purpose".to_string() }),
         }
-        
     }
-} 
 
-derive_policied!(i64, Policiedi64);
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+// ------------------- LIBRARY POLICIED STRUCTS --------------------------------------
+
+/*
+The library's policied types are aliases of the generic GPolicied<T>, which provides 
+everything derive_policied! used to generate for them (see generic_policied.rs for 
+the String, Vec and Option specific methods). Types generated by derive_policied! 
+in user code convert to and from GPolicied with From/Into.
+*/
+pub type PoliciedString = GPolicied<String>;
+
+pub type Policiedi64 = GPolicied<i64>;
+
+pub type PoliciedStringVec = GPoliciedVec<String>;
 
-derive_policied_vec!(PoliciedStringVec, String, PoliciedString);
+// Keeps each string's policy separate, see PoliciedElemVec.
+pub type PoliciedStringElemVec = PoliciedElemVec<String>;
 
-derive_policied_option!(PoliciedStringOption, String, PoliciedString);
+pub type PoliciedStringOption = GPolicied<Option<String>>;
@@ -0,0 +1,68 @@
+#![cfg(feature = "tokio")]
+
+use beaver::async_beaverio::{AsyncBeaverReader, AsyncBeaverWriter};
+use beaver::filter::Context;
+use beaver::generic_policied::GPolicied;
+use beaver::policy::{NonePolicy, Policied, PoliciedString, Policy, PurposePolicy};
+use tokio::io::AsyncReadExt;
+
+fn ctxt() -> Context {
+    Context::KVContext(std::collections::HashMap::new())
+}
+
+fn grading_only() -> Box<dyn Policy> {
+    Box::new(PurposePolicy::make(vec!["grading".to_string()]))
+}
+
+#[tokio::test]
+async fn json_round_trip() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut writer = AsyncBeaverWriter::safe_create(client, ctxt());
+    let grade: GPolicied<i64> = GPolicied::make(93, Box::new(NonePolicy));
+    writer.safe_write_json(&grade).await.unwrap();
+    writer.flush().await.unwrap();
+    drop(writer);
+
+    let mut reader = AsyncBeaverReader::safe_create(server);
+    let read: GPolicied<i64> = reader.safe_deserialize_line().await.unwrap().unwrap();
+    assert_eq!(*read.unsafe_borrow_inner(), 93);
+    assert!(reader.safe_deserialize_line::<GPolicied<i64>>().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn denied_write_sends_nothing() {
+    let (client, mut server) = tokio::io::duplex(1024);
+    let mut writer = AsyncBeaverWriter::safe_create(client, ctxt());
+    let grade: GPolicied<i64> = GPolicied::make(93, grading_only());
+    assert!(writer.safe_write_json(&grade).await.is_err());
+    let id: PoliciedString = GPolicied::make("malte".to_string(), grading_only());
+    assert!(writer.safe_write_serialized(&id).await.is_err());
+    writer.shutdown().await.unwrap();
+
+    let mut received = Vec::new();
+    server.read_to_end(&mut received).await.unwrap();
+    assert!(received.is_empty());
+}
+
+#[tokio::test]
+async fn purpose_permits_write() {
+    let (client, mut server) = tokio::io::duplex(1024);
+    let mut writer = AsyncBeaverWriter::safe_create_with_purpose(client, ctxt(), "grading");
+    let id: PoliciedString = GPolicied::make("malte".to_string(), grading_only());
+    writer.safe_write_serialized(&id).await.unwrap();
+    writer.shutdown().await.unwrap();
+
+    let mut received = String::new();
+    server.read_to_string(&mut received).await.unwrap();
+    assert_eq!(received, "malte\n");
+}
+
+#[tokio::test]
+async fn multi_hop_needs_a_hop() {
+    let (client, _server) = tokio::io::duplex(1024);
+    let err = AsyncBeaverWriter::safe_create_multi_hop(client, Vec::new()).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let (client, _server) = tokio::io::duplex(1024);
+    assert!(AsyncBeaverWriter::safe_create_multi_hop(client, vec![ctxt()]).is_ok());
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use beaver::beaverio::{display_or_redact, BeaverBufReader, BeaverBufWriter, BeaverFile, REDACTED};
+use beaver::filter::{ConsoleContext, Context, FileContext, PipeContext, UnixSocketContext};
+use beaver::generic_policied::GPolicied;
+use beaver::policy::{MergePolicy, NonePolicy, Policied, PoliciedString, Policiedi64, Policy, PolicyError, PurposePolicy};
+use beaver_derive::Policied;
+use beaver::{beaver_eprintln, beaver_println};
+use serde::{Deserialize, Serialize};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("beaver-io-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Only lets data reach files with the given name, wherever they are.
+#[derive(Clone, Serialize, Deserialize)]
+struct FileNamePolicy {
+    file_name: String,
+}
+
+#[typetag::serde]
+impl Policy for FileNamePolicy {
+    fn check(&self, ctxt: &Context) -> Result<(), PolicyError> {
+        match ctxt.without_purpose() {
+            Context::File(fc) if fc.file_name == self.file_name => Ok(()),
+            _ => Err(PolicyError { message: format!("Only {} may hold this", self.file_name) }),
+        }
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+fn only_in(file_name: &str) -> Box<dyn Policy> {
+    Box::new(FileNamePolicy { file_name: file_name.to_string() })
+}
+
+// Only lets data reach Unix sockets whose peer runs as the given user.
+#[cfg(unix)]
+#[derive(Clone, Serialize, Deserialize)]
+struct PeerUidPolicy {
+    uid: u32,
+}
+
+#[cfg(unix)]
+#[typetag::serde]
+impl Policy for PeerUidPolicy {
+    fn check(&self, ctxt: &Context) -> Result<(), PolicyError> {
+        match ctxt {
+            Context::UnixSocket(usc) if usc.peer.map(|p| p.uid) == Some(self.uid) => Ok(()),
+            _ => Err(PolicyError { message: format!("Peer must run as uid {}", self.uid) }),
+        }
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+#[cfg(unix)]
+fn mkfifo(path: &Path) {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn pipe_round_trip() {
+    let fifo = scratch_dir("fifo").join("grades");
+    mkfifo(&fifo);
+    let reader_path = fifo.clone();
+    let reader = std::thread::spawn(move || {
+        let mut received = String::new();
+        fs::File::open(reader_path).unwrap().read_to_string(&mut received).unwrap();
+        received
+    });
+
+    let mut writer = BeaverBufWriter::safe_create_pipe(&fifo).unwrap();
+    let id: PoliciedString = GPolicied::make("malte".to_string(), Box::new(NonePolicy));
+    writer.safe_write_serialized(&id).unwrap();
+    drop(writer);
+    assert_eq!(reader.join().unwrap(), "malte\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn pipe_context_checks_opened_file() {
+    let dir = scratch_dir("notfifo");
+    let plain = dir.join("plain");
+    fs::write(&plain, "").unwrap();
+    assert!(BeaverBufWriter::safe_create_pipe(&plain).is_err());
+
+    // A FIFO opened through one path is rejected if the path now names another file.
+    let fifo = dir.join("fifo");
+    mkfifo(&fifo);
+    let opened = fs::OpenOptions::new().read(true).write(true).open(&fifo).unwrap();
+    assert!(PipeContext::from_file(&opened, &fifo).is_ok());
+    assert!(PipeContext::from_file(&opened, &plain).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_context_has_peer_credentials() {
+    use std::os::unix::net::UnixStream;
+    let (ours, _theirs) = UnixStream::pair().unwrap();
+    let usc = UnixSocketContext::from_stream(&ours).unwrap();
+    assert_eq!(usc.socket_path, None);
+    let peer = usc.peer.unwrap();
+    assert_eq!(peer.uid, unsafe { libc::getuid() });
+    assert_eq!(peer.gid, unsafe { libc::getgid() });
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_writer_checks_the_peer() {
+    use std::os::unix::net::UnixStream;
+    let uid = unsafe { libc::getuid() };
+    let (ours, mut theirs) = UnixStream::pair().unwrap();
+    let mut writer = BeaverBufWriter::safe_create_unix(ours).unwrap();
+    let other_user: PoliciedString = GPolicied::make("livia".to_string(), Box::new(PeerUidPolicy { uid: uid.wrapping_add(1) }));
+    assert!(writer.safe_write_serialized(&other_user).is_err());
+    let same_user: PoliciedString = GPolicied::make("malte".to_string(), Box::new(PeerUidPolicy { uid }));
+    writer.safe_write_serialized(&same_user).unwrap();
+    drop(writer);
+
+    let mut received = String::new();
+    theirs.read_to_string(&mut received).unwrap();
+    assert_eq!(received, "malte\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_connect_records_the_path() {
+    use std::os::unix::net::UnixListener;
+    let path = scratch_dir("socket").join("grades.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let mut writer = BeaverBufWriter::safe_connect_unix(&path).unwrap();
+    let (mut accepted, _) = listener.accept().unwrap();
+
+    let id: PoliciedString = GPolicied::make("malte".to_string(), Box::new(PeerUidPolicy { uid: unsafe { libc::getuid() } }));
+    writer.safe_write_serialized(&id).unwrap();
+    drop(writer);
+    let mut received = String::new();
+    accepted.read_to_string(&mut received).unwrap();
+    assert_eq!(received, "malte\n");
+
+    let client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+    let usc = UnixSocketContext::from_stream(&client).unwrap();
+    assert_eq!(usc.socket_path, Some(path.to_string_lossy().into_owned()));
+}
+
+#[test]
+fn console_output_is_redacted() {
+    let stdout = Context::Console(ConsoleContext::Stdout);
+    let id: PoliciedString = GPolicied::make("malte".to_string(), Box::new(NonePolicy));
+    let grade: GPolicied<i64> = GPolicied::make(93, Box::new(PurposePolicy::make(vec!["grading".to_string()])));
+    assert_eq!(display_or_redact(&id, &stdout), "malte");
+    assert_eq!(display_or_redact(&grade, &stdout), REDACTED);
+    assert_eq!(display_or_redact(&grade, &stdout.with_purpose("grading")), "93");
+
+    // The macros take the same arguments as println!.
+    beaver_println!("{} got {}", id, grade);
+    beaver_eprintln!("{} got {}", id, grade);
+}
+
+#[test]
+fn beaver_file_context_comes_from_the_file() {
+    let dir = scratch_dir("file").canonicalize().unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+    let file = BeaverFile::create(dir.join("sub").join("..").join("malte")).unwrap();
+    assert_eq!(file.context().file_name, "malte");
+    assert_eq!(file.context().path, dir.to_string_lossy());
+
+    let grade: GPolicied<i64> = GPolicied::make(93, only_in("malte"));
+    let mut writer = file.into_writer();
+    writer.safe_write_json(&grade).unwrap();
+    drop(writer);
+
+    let mut reader = BeaverFile::open(dir.join("malte")).unwrap().into_reader();
+    let read: GPolicied<i64> = reader.safe_deserialize_line();
+    assert_eq!(*read.unsafe_borrow_inner(), 93);
+    assert!(read.get_policy().check(&Context::File(FileContext::from_path(dir.join("malte")).unwrap())).is_ok());
+    assert!(BeaverFile::open(dir.join("missing")).is_err());
+}
+
+#[test]
+fn beaver_file_writers_check_the_policy() {
+    let dir = scratch_dir("file-denied");
+    let grade: GPolicied<i64> = GPolicied::make(93, only_in("malte"));
+    let mut writer = BeaverFile::create(dir.join("livia")).unwrap().into_writer();
+    assert!(writer.safe_write_json(&grade).is_err());
+    drop(writer);
+    assert_eq!(fs::read_to_string(dir.join("livia")).unwrap(), "");
+
+    // Every hop before the file is checked as well.
+    let mut writer = BeaverFile::create(dir.join("malte")).unwrap().into_writer_via(vec![Context::Console(ConsoleContext::Stdout)]);
+    assert!(writer.safe_write_json(&grade).is_err());
+
+    let graded: Box<dyn Policy> = Box::new(PurposePolicy::make(vec!["grading".to_string()]));
+    let grade: GPolicied<i64> = GPolicied::make(93, Box::new(MergePolicy::make(only_in("malte"), graded)));
+    let mut writer = BeaverFile::create(dir.join("malte")).unwrap().into_writer();
+    assert!(writer.safe_write_json(&grade).is_err());
+    let mut writer = BeaverFile::create(dir.join("malte")).unwrap().into_writer_with_purpose("grading");
+    writer.safe_write_json(&grade).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_context_checks_opened_file() {
+    let dir = scratch_dir("file-swap");
+    let opened = fs::File::create(dir.join("malte")).unwrap();
+    fs::write(dir.join("livia"), "").unwrap();
+    assert!(FileContext::from_file(&opened, dir.join("malte")).is_ok());
+    assert!(FileContext::from_file(&opened, dir.join("livia")).is_err());
+
+    // The file is replaced after it was opened; the path now names another file.
+    fs::rename(dir.join("livia"), dir.join("malte")).unwrap();
+    assert!(FileContext::from_file(&opened, dir.join("malte")).is_err());
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedGrade)]
+pub struct Grade {
+    #[policy_protected(PoliciedString)]
+    pub student_id: String,
+    #[policy_protected(Policiedi64)]
+    pub grade: i64,
+}
+
+#[test]
+fn safe_write_json_round_trips_policied_values() {
+    let mut buf = Vec::new();
+    let mut writer = BeaverBufWriter::safe_create(&mut buf, Context::File(FileContext {
+        file_name: "malte".to_string(),
+        path: "/srv/grades".to_string(),
+    }));
+    let grade: GPolicied<i64> = GPolicied::make(93, only_in("malte"));
+    writer.safe_write_json(&grade).unwrap();
+    let pg = PoliciedGrade::make_decomposed_unpolicied("malte".to_string(), 93, only_in("malte"));
+    writer.safe_write_json(&pg).unwrap();
+
+    // Denied writes leave nothing behind.
+    assert!(writer.safe_write_json(&GPolicied::make(71i64, only_in("livia"))).is_err());
+    let other = PoliciedGrade::make_decomposed_unpolicied("livia".to_string(), 71, only_in("livia"));
+    assert!(writer.safe_write_json(&other).is_err());
+    drop(writer);
+    assert_eq!(buf.iter().filter(|b| **b == b'\n').count(), 2);
+
+    let mut reader = BeaverBufReader::safe_create(buf.as_slice());
+    let read: GPolicied<i64> = reader.safe_deserialize_line();
+    assert_eq!(*read.unsafe_borrow_inner(), 93);
+    assert!(read.export_check_borrow(&Context::Console(ConsoleContext::Stdout)).is_err());
+    let read: PoliciedGrade = reader.safe_deserialize_line();
+    assert_eq!(read.grade().unsafe_export(), 93);
+    assert!(read.student_id().export_check(&Context::Console(ConsoleContext::Stdout)).is_err());
+}
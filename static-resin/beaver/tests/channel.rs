@@ -0,0 +1,54 @@
+use std::thread;
+
+use beaver::channel;
+use beaver::filter::{ChannelContext, Context};
+use beaver::generic_policied::GPolicied;
+use beaver::policy::{NonePolicy, Policied, Policy, PurposePolicy};
+
+fn grader() -> Context {
+    Context::Channel(ChannelContext { name: "grader".to_string() })
+}
+
+fn grading_only() -> Box<dyn Policy> {
+    Box::new(PurposePolicy::make(vec!["grading".to_string()]))
+}
+
+#[test]
+fn send_across_threads() {
+    let (tx, rx) = channel::channel(grader());
+    let worker = thread::spawn(move || {
+        tx.send(GPolicied::make(93i64, Box::new(NonePolicy))).unwrap();
+    });
+    worker.join().unwrap();
+    assert_eq!(*rx.recv().unwrap().unsafe_borrow_inner(), 93);
+}
+
+#[test]
+fn denied_value_is_not_enqueued() {
+    let (tx, rx) = channel::channel(grader());
+    match tx.send(GPolicied::make(93i64, grading_only())) {
+        Err(channel::PoliciedSendError::Denied(v, _)) => assert_eq!(*v.unsafe_borrow_inner(), 93),
+        _ => panic!("expected the send to be denied"),
+    }
+    drop(tx);
+    assert!(rx.recv().is_err());
+}
+
+#[test]
+fn purpose_permits_send() {
+    let (tx, rx) = channel::sync_channel(grader().with_purpose("grading"), 1);
+    tx.send(GPolicied::make(93i64, grading_only())).unwrap();
+    let received: GPolicied<i64> = rx.recv().unwrap();
+    assert!(received.get_policy().check(&grader()).is_err());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn tokio_channel_checks_policy() {
+    let (tx, mut rx) = channel::tokio_channel(grader(), 4);
+    assert!(tx.send(GPolicied::make(1i64, grading_only())).await.is_err());
+    tx.send(GPolicied::make(2i64, Box::new(NonePolicy))).await.unwrap();
+    drop(tx);
+    assert_eq!(*rx.recv().await.unwrap().unsafe_borrow_inner(), 2);
+    assert!(rx.recv().await.is_none());
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use beaver::filter::Context;
+use beaver::generic_policied::GPolicied;
+use beaver::policy::{MergePolicy, NonePolicy, Policied, PoliciedString, Policiedi64, Policy, PolicyError};
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+fn ctxt() -> Context {
+    Context::KVContext(HashMap::new())
+}
+
+// Only lets data reach key-value contexts whose "role" is the given one.
+#[derive(Clone, Serialize, Deserialize)]
+struct RolePolicy {
+    role: String,
+}
+
+#[typetag::serde]
+impl Policy for RolePolicy {
+    fn check(&self, ctxt: &Context) -> Result<(), PolicyError> {
+        match ctxt {
+            Context::KVContext(kv) if kv.get("role") == Some(&self.role) => Ok(()),
+            _ => Err(PolicyError { message: format!("Only the {} role may see this", self.role) }),
+        }
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+fn as_role(role: &str) -> Context {
+    let mut kv = HashMap::new();
+    kv.insert("role".to_string(), role.to_string());
+    Context::KVContext(kv)
+}
+
+fn graders_only() -> Box<dyn Policy> {
+    Box::new(RolePolicy { role: "grader".to_string() })
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedGrade)]
+pub struct Grade {
+    #[policy_protected(PoliciedString)]
+    pub student_id: String,
+
+    #[policy_protected(Policiedi64)]
+    pub grade: i64,
+
+    pub term: String,
+}
+
+#[test]
+fn make_decomposed_merges_field_policies() {
+    let student_id: PoliciedString = GPolicied::make("malte".to_string(), graders_only());
+    let grade: Policiedi64 = GPolicied::make(93, Box::new(NonePolicy));
+    let pg = PoliciedGrade::make_decomposed(student_id, grade, "fall".to_string(), Box::new(NonePolicy));
+
+    assert!(pg.export_check_borrow(&ctxt()).is_err());
+    // The grade carries the student id's policy too.
+    assert!(pg.grade().export_check(&ctxt()).is_err());
+    assert_eq!(pg.grade().export_check(&as_role("grader")).unwrap(), 93);
+    let grade = pg.export_check(&as_role("grader")).unwrap();
+    assert_eq!((grade.student_id.as_str(), grade.term.as_str()), ("malte", "fall"));
+}
+
+#[test]
+fn make_decomposed_unpolicied_uses_given_policy() {
+    let pg = PoliciedGrade::make_decomposed_unpolicied("malte".to_string(), 93, "fall".to_string(), graders_only());
+    assert!(pg.student_id().export_check(&ctxt()).is_err());
+    let mut pg = pg;
+    pg.remove_policy();
+    assert_eq!(pg.student_id().export_check(&ctxt()).unwrap(), "malte");
+}
+
+#[test]
+fn policied_struct_round_trips_through_json() {
+    let pg = PoliciedGrade::make_decomposed_unpolicied("malte".to_string(), 93, "fall".to_string(), graders_only());
+    let json = serde_json::to_string(&pg).unwrap();
+    let read: PoliciedGrade = serde_json::from_str(&json).unwrap();
+    assert!(read.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(read.grade().export_check(&as_role("grader")).unwrap(), 93);
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedFieldGrade, per_field)]
+pub struct FieldGrade {
+    #[policy_protected(PoliciedString)]
+    pub student_id: String,
+
+    #[policy_protected(Policiedi64)]
+    pub grade: i64,
+}
+
+#[test]
+fn per_field_getters_keep_their_own_policy() {
+    let student_id: PoliciedString = GPolicied::make("malte".to_string(), graders_only());
+    let grade: Policiedi64 = GPolicied::make(93, Box::new(NonePolicy));
+    let pg = PoliciedFieldGrade::make_decomposed(student_id, grade, Box::new(NonePolicy));
+
+    assert_eq!(pg.grade().export_check(&ctxt()).unwrap(), 93);
+    assert!(pg.student_id().export_check(&ctxt()).is_err());
+    // Exporting the whole struct checks every field's policy.
+    assert!(pg.export_check_borrow(&ctxt()).is_err());
+    assert!(pg.export_check_borrow(&as_role("grader")).is_ok());
+}
+
+#[test]
+fn per_field_constructor_policy_applies_to_every_field() {
+    let student_id: PoliciedString = GPolicied::make("malte".to_string(), Box::new(NonePolicy));
+    let grade: Policiedi64 = GPolicied::make(93, Box::new(NonePolicy));
+    let mut pg = PoliciedFieldGrade::make_decomposed(student_id, grade, graders_only());
+    assert!(pg.grade().export_check(&ctxt()).is_err());
+    assert!(pg.student_id().export_check(&ctxt()).is_err());
+
+    pg.remove_policy();
+    assert_eq!(pg.grade().export_check(&ctxt()).unwrap(), 93);
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedSubmission)]
+pub enum Submission {
+    Text(#[policy_protected(PoliciedString)] String),
+    File(#[policy_protected(GPolicied<Vec<u8>>)] Vec<u8>),
+    Graded {
+        #[policy_protected]
+        grade: i64,
+        #[policy_protected]
+        comment: String,
+    },
+    Missing,
+}
+
+#[test]
+fn enum_getters_match_the_stored_variant() {
+    let text: PoliciedString = GPolicied::make("essay".to_string(), graders_only());
+    let ps = PoliciedSubmission::make_text(text, Box::new(NonePolicy));
+    assert!(ps.file().is_none());
+    assert!(ps.text().unwrap().export_check(&ctxt()).is_err());
+    assert_eq!(ps.text().unwrap().export_check(&as_role("grader")).unwrap(), "essay");
+    assert_eq!(**ps.text_ref().unwrap().unsafe_borrow_inner(), "essay");
+
+    let ps = PoliciedSubmission::make_graded(
+        GPolicied::make(93, Box::new(NonePolicy)),
+        GPolicied::make("well done".to_string(), Box::new(NonePolicy)),
+        graders_only(),
+    );
+    assert!(ps.text().is_none());
+    assert!(ps.graded_grade().unwrap().export_check(&ctxt()).is_err());
+    assert_eq!(ps.graded_comment().unwrap().unsafe_export(), "well done");
+
+    let ps = PoliciedSubmission::make_missing(Box::new(NonePolicy));
+    assert!(matches!(ps.unsafe_export(), Submission::Missing));
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedStudentId)]
+pub struct StudentId(#[policy_protected] String, u32);
+
+#[test]
+fn tuple_struct_fields_are_named_by_index() {
+    let id: PoliciedString = GPolicied::make("malte".to_string(), graders_only());
+    let psi = PoliciedStudentId::make_decomposed(id, 2021, Box::new(NonePolicy));
+    assert!(psi._0().export_check(&ctxt()).is_err());
+    let StudentId(name, year) = psi.export_check(&as_role("grader")).unwrap();
+    assert_eq!((name.as_str(), year), ("malte", 2021));
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedRecord)]
+pub struct Record<T> {
+    #[policy_protected(GPolicied<T>)]
+    pub value: T,
+    pub version: u32,
+}
+
+#[test]
+fn generic_struct_gets_generic_wrapper() {
+    let value: GPolicied<Vec<i64>> = GPolicied::make(vec![93, 88], graders_only());
+    let pr: PoliciedRecord<Vec<i64>> = PoliciedRecord::make_decomposed(value, 1, Box::new(NonePolicy));
+    assert!(pr.value().export_check(&ctxt()).is_err());
+    assert_eq!(pr.value().export_check(&as_role("grader")).unwrap(), vec![93, 88]);
+
+    let json = serde_json::to_string(&pr).unwrap();
+    let read: PoliciedRecord<Vec<i64>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.unsafe_export().version, 1);
+}
+
+#[test]
+fn borrowing_getters_and_setters() {
+    let mut pg = PoliciedGrade::make_decomposed_unpolicied("malte".to_string(), 93, "fall".to_string(), Box::new(NonePolicy));
+    assert_eq!(**pg.grade_ref().export_check_borrow(&ctxt()).unwrap(), 93);
+
+    // The new value's policy is merged into the whole struct.
+    pg.set_grade(GPolicied::make(95, graders_only())).unwrap();
+    assert_eq!(**pg.grade_ref().unsafe_borrow_inner(), 95);
+    assert!(pg.student_id_ref().export_check_borrow(&ctxt()).is_err());
+    assert!(pg.export_check_borrow(&ctxt()).is_err());
+}
+
+#[test]
+fn per_field_setter_only_taints_its_field() {
+    let mut pg = PoliciedFieldGrade::make_decomposed(
+        GPolicied::make("malte".to_string(), Box::new(NonePolicy)),
+        GPolicied::make(93, Box::new(NonePolicy)),
+        Box::new(NonePolicy),
+    );
+    pg.set_grade(GPolicied::make(95, graders_only())).unwrap();
+    assert!(pg.grade_ref().export_check_borrow(&ctxt()).is_err());
+    assert!(pg.student_id_ref().export_check_borrow(&ctxt()).is_ok());
+    assert!(pg.export_check_borrow(&ctxt()).is_err());
+}
+
+#[test]
+fn tuple_struct_accessors() {
+    let mut psi = PoliciedStudentId::make_decomposed_unpolicied("malte".to_string(), 2021, Box::new(NonePolicy));
+    assert_eq!(*psi.ref_0().unsafe_borrow_inner(), "malte");
+    psi.set_0(GPolicied::make("livia".to_string(), graders_only())).unwrap();
+    assert!(psi.ref_0().export_check_borrow(&ctxt()).is_err());
+    assert_eq!(psi._0().unsafe_export(), "livia");
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedRoster)]
+pub struct Roster {
+    #[policy_protected]
+    pub students: Vec<Grade>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedCourse)]
+pub struct Course {
+    #[policy_protected]
+    pub roster: Roster,
+    pub name: String,
+}
+
+#[test]
+fn nested_getters_chain_through_has_policied() {
+    let grade = Grade { student_id: "malte".to_string(), grade: 93, term: "fall".to_string() };
+    let roster: PoliciedRoster = PoliciedRoster::make_decomposed_unpolicied(vec![grade], graders_only());
+    let course = PoliciedCourse::make_decomposed(roster, "cs242".to_string(), Box::new(NonePolicy));
+
+    let missing = Grade { student_id: String::new(), grade: 0, term: String::new() };
+    let first: PoliciedGrade = course.roster().students().get(0).unwrap_or(missing.clone()).into_policied();
+    assert!(first.grade().export_check(&ctxt()).is_err());
+    assert_eq!(first.grade().export_check(&as_role("grader")).unwrap(), 93);
+
+    // Probing past the end doesn't reveal the length either.
+    let second = course.roster().students().get(1);
+    assert!(second.is_none().export_check(&ctxt()).is_err());
+    assert!(second.is_none().export_check(&as_role("grader")).unwrap());
+    let second: PoliciedGrade = second.unwrap_or(missing).into_policied();
+    assert!(second.export_check_borrow(&ctxt()).is_err());
+}
@@ -0,0 +1,95 @@
+use beaver::filter::{ConsoleContext, Context, FileContext, RemoteConnectContext};
+use beaver::policy::Policy;
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+fn file(dir: &str, name: &str) -> Context {
+    Context::File(FileContext { file_name: name.to_string(), path: dir.to_string() })
+}
+
+fn remote(ip: &str) -> Context {
+    Context::ClientNetwork(RemoteConnectContext { remote_ip_address: ip.parse().unwrap(), port: 443 })
+}
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(name = student_id, path = grade_dir, message = "not the student's file")]
+#[allow_remote_ip(student_ip, instructor_ip)]
+pub struct GradePolicy {
+    pub student_id: String,
+    pub grade_dir: String,
+    pub student_ip: Option<String>,
+    pub instructor_ip: Vec<String>,
+}
+
+fn grade_policy() -> GradePolicy {
+    GradePolicy {
+        student_id: "malte".to_string(),
+        grade_dir: "/srv/grades".to_string(),
+        student_ip: Some("10.0.0.1".to_string()),
+        instructor_ip: vec!["10.0.0.2".to_string(), "10.0.0.3".to_string()],
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(name = owner)]
+#[allow_other]
+pub struct OwnerFilePolicy {
+    pub owner: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[deny_other(message = "nothing leaves")]
+pub struct SealedPolicy {}
+
+#[test]
+fn each_listed_file_field_is_a_rule() {
+    let policy = grade_policy();
+    assert!(policy.check(&file("/home/malte", "malte")).is_ok());
+    assert!(policy.check(&file("/srv/grades/2020", "livia")).is_ok());
+    let err = policy.check(&file("/home/livia", "livia")).unwrap_err();
+    assert_eq!(err.message, "not the student's file");
+}
+
+#[test]
+fn each_listed_ip_field_is_a_rule() {
+    let policy = grade_policy();
+    assert!(policy.check(&remote("10.0.0.1")).is_ok());
+    assert!(policy.check(&remote("10.0.0.2")).is_ok());
+    assert!(policy.check(&remote("10.0.0.3")).is_ok());
+    assert!(policy.check(&remote("10.0.0.4")).is_err());
+}
+
+#[test]
+fn other_contexts_are_denied_by_default() {
+    let policy = grade_policy();
+    let err = policy.check(&Context::Console(ConsoleContext::Stdout)).unwrap_err();
+    assert_eq!(err.message, "GradePolicy does not allow this flow");
+    let err = SealedPolicy {}.check(&Context::Console(ConsoleContext::Stdout)).unwrap_err();
+    assert_eq!(err.message, "nothing leaves");
+}
+
+#[test]
+fn allow_other_lets_unmatched_contexts_through() {
+    let policy = OwnerFilePolicy { owner: "malte".to_string() };
+    assert!(policy.check(&Context::Console(ConsoleContext::Stdout)).is_ok());
+    assert!(policy.check(&file("/home/livia", "livia")).is_err());
+}
+
+#[test]
+fn purpose_and_composite_contexts_are_unwrapped() {
+    let policy = grade_policy();
+    assert!(policy.check(&remote("10.0.0.1").with_purpose("grading")).is_ok());
+    let flow = Context::chain(vec![remote("10.0.0.2"), file("/home/malte", "malte")]);
+    assert!(policy.check(&flow).is_ok());
+    let flow = Context::chain(vec![remote("10.0.0.2"), file("/home/livia", "livia")]);
+    assert!(policy.check(&flow).is_err());
+}
+
+#[test]
+fn derived_policy_round_trips_as_trait_object() {
+    let boxed: Box<dyn Policy> = Box::new(grade_policy());
+    let json = serde_json::to_string(&boxed).unwrap();
+    let read: Box<dyn Policy> = serde_json::from_str(&json).unwrap();
+    assert!(read.check(&remote("10.0.0.3")).is_ok());
+    assert!(read.check(&remote("10.0.0.4")).is_err());
+}
@@ -0,0 +1,7 @@
+// Malformed derive input must fail with a spanned error, not a panic. The expected
+// diagnostics live next to each case in tests/ui; regenerate them with TRYBUILD=overwrite.
+#[test]
+fn derive_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use beaver::beaverio::BeaverBufWriter;
+use beaver::filter::{CompositeContext, ConsoleContext, Context, CtxKey, FileContext};
+use beaver::policy::{NonePolicy, Policy};
+use beaver::kv_ctx;
+
+const USER_ID: CtxKey<u64> = CtxKey::new("user_id");
+const COURSE: CtxKey<String> = CtxKey::new("course");
+const USER_NAME: CtxKey<String> = CtxKey::new("user_id");
+
+// A fresh directory under the system temp dir, unique to this process and test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("beaver-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+#[test]
+fn from_path_resolves_dot_dot() {
+    let dir = scratch_dir("dotdot");
+    fs::create_dir_all(dir.join("grades")).unwrap();
+    fs::write(dir.join("grades/malte.json"), "{}").unwrap();
+
+    let fc = FileContext::from_path(dir.join("grades/../grades/malte.json")).unwrap();
+    assert_eq!(fc.file_name, "malte.json");
+    assert_eq!(fc.full_path(), dir.join("grades/malte.json"));
+    assert!(FileContext::from_path(dir.join("missing")).is_err());
+}
+
+#[test]
+fn is_within_compares_components() {
+    let dir = scratch_dir("within");
+    fs::create_dir_all(dir.join("database")).unwrap();
+    fs::write(dir.join("database/x"), "").unwrap();
+
+    let fc = FileContext::from_path(dir.join("database/x")).unwrap();
+    assert!(fc.is_within(&dir));
+    assert!(fc.is_within(dir.join("database")));
+    assert!(!fc.is_within(dir.join("data")));
+}
+
+#[test]
+fn matches_glob_respects_separators() {
+    let dir = scratch_dir("glob");
+    fs::create_dir_all(dir.join("2020/fall")).unwrap();
+    fs::write(dir.join("2020/fall/malte.json"), "{}").unwrap();
+
+    let fc = FileContext::from_path(dir.join("2020/fall/malte.json")).unwrap();
+    let root = dir.to_string_lossy();
+    assert!(fc.matches_glob(&format!("{}/**/*.json", root)));
+    assert!(fc.matches_glob(&format!("{}/*/fall/*.json", root)));
+    assert!(!fc.matches_glob(&format!("{}/*.json", root)));
+    assert!(!fc.matches_glob(&format!("{}/**/*.txt", root)));
+    assert!(!fc.matches_glob("[invalid"));
+}
+
+#[test]
+fn kv_ctx_with_literal_keys_stringifies() {
+    match kv_ctx!("user_id" => 42, "course" => "cs242") {
+        Context::KVContext(m) => {
+            assert_eq!(m["user_id"], "42");
+            assert_eq!(m["course"], "cs242");
+        }
+        _ => panic!("expected a KVContext"),
+    }
+}
+
+#[test]
+fn kv_ctx_with_typed_keys() {
+    match kv_ctx!(USER_ID => 42, COURSE => "cs242".to_string()) {
+        Context::TypedKVContext(m) => {
+            assert_eq!(m.get(&USER_ID), Some(&42));
+            assert_eq!(m.get(&COURSE).map(String::as_str), Some("cs242"));
+            // Same name, different type: the value is not handed out.
+            assert!(m.get(&USER_NAME).is_none());
+            assert!(m.contains(&USER_ID));
+        }
+        _ => panic!("expected a TypedKVContext"),
+    }
+}
+
+#[test]
+fn composite_purpose_is_shared_by_all_hops() {
+    let hops = || vec![kv_ctx!("hop" => 1), Context::Console(ConsoleContext::Stdout)];
+    let writer = BeaverBufWriter::safe_create_with_purpose(Vec::new(), Context::chain(hops()), "billing");
+    assert_eq!(writer.purpose(), Some("billing"));
+
+    assert_eq!(Context::chain(hops()).purpose(), None);
+    let mixed = Context::chain(vec![
+        kv_ctx!("hop" => 1).with_purpose("billing"),
+        Context::Console(ConsoleContext::Stdout).with_purpose("marketing"),
+    ]);
+    assert_eq!(mixed.purpose(), None);
+    let partial = Context::chain(vec![kv_ctx!("hop" => 1).with_purpose("billing"), Context::Console(ConsoleContext::Stdout)]);
+    assert_eq!(partial.purpose(), None);
+}
+
+#[test]
+fn flows_without_hops_are_denied() {
+    // CompositeContext is public, so an empty one can still be built by hand.
+    let empty = Context::Composite(CompositeContext { hops: Vec::new() });
+    assert!(NonePolicy.check_flow(&empty).is_err());
+    let err = BeaverBufWriter::safe_create_multi_hop(Vec::new(), Vec::new()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[should_panic(expected = "at least one hop")]
+fn chain_rejects_an_empty_flow() {
+    Context::chain(vec![Context::Composite(CompositeContext { hops: Vec::new() })]);
+}
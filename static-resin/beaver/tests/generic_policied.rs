@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use beaver::filter::Context;
+use beaver::generic_policied::{GPolicied, PoliciedElemHashMap, PoliciedElemVec};
+use beaver::policy::{MergePolicy, NonePolicy, Policied, PoliciedStringVec, Policy, PolicyError};
+use serde::{Deserialize, Serialize};
+
+fn ctxt() -> Context {
+    Context::KVContext(HashMap::new())
+}
+
+// Only lets data reach key-value contexts whose "role" is the given one.
+#[derive(Clone, Serialize, Deserialize)]
+struct RolePolicy {
+    role: String,
+}
+
+#[typetag::serde]
+impl Policy for RolePolicy {
+    fn check(&self, ctxt: &Context) -> Result<(), PolicyError> {
+        match ctxt {
+            Context::KVContext(kv) if kv.get("role") == Some(&self.role) => Ok(()),
+            _ => Err(PolicyError { message: format!("Only the {} role may see this", self.role) }),
+        }
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+fn as_role(role: &str) -> Context {
+    let mut kv = HashMap::new();
+    kv.insert("role".to_string(), role.to_string());
+    Context::KVContext(kv)
+}
+
+fn graders_only() -> Box<dyn Policy> {
+    Box::new(RolePolicy { role: "grader".to_string() })
+}
+
+#[test]
+fn vec_push_and_push_policy() {
+    let mut ids: PoliciedStringVec = GPolicied::make(Vec::new(), Box::new(NonePolicy));
+    ids.push_unpolicied("malte".to_string());
+    assert!(ids.export_check_borrow(&ctxt()).is_ok());
+
+    ids.push_policy(GPolicied::make("livia".to_string(), graders_only()));
+    assert!(ids.export_check_borrow(&ctxt()).is_err());
+    let ids = ids.export_check(&as_role("grader")).unwrap();
+    assert_eq!(ids, vec!["malte".to_string(), "livia".to_string()]);
+}
+
+#[test]
+fn push_merges_into_an_inferred_vec() {
+    // The element type is inferred from push, so it must be the policied value's inner type.
+    let mut grades = GPolicied::make_default(Vec::new());
+    grades.push(GPolicied::make(93i64, graders_only()));
+    assert!(grades.export_check_borrow(&ctxt()).is_err());
+    let grades: Vec<i64> = grades.export_check(&as_role("grader")).unwrap();
+    assert_eq!(grades, vec![93]);
+}
+
+fn bursars_only() -> Box<dyn Policy> {
+    Box::new(RolePolicy { role: "bursar".to_string() })
+}
+
+fn open<T>(value: T) -> GPolicied<T> {
+    GPolicied::make(value, Box::new(NonePolicy))
+}
+
+#[test]
+fn operators_merge_both_policies() {
+    let grade: GPolicied<i64> = GPolicied::make(90, graders_only());
+    let bonus = open(3);
+    let total = &grade + &bonus;
+    assert!(total.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(*total.export_check_borrow(&as_role("grader")).unwrap(), 93);
+
+    // Owned operands, assignment and unary operators keep the policy as well.
+    let mut total = -(grade - bonus);
+    total += open(100);
+    assert!(total.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(total.export_check(&as_role("grader")).unwrap(), 13);
+
+    let fee: GPolicied<i64> = GPolicied::make(10, bursars_only());
+    let mixed = open(1) * fee + GPolicied::make(2, graders_only());
+    assert!(mixed.export_check_borrow(&as_role("grader")).is_err());
+    assert!(mixed.export_check_borrow(&as_role("bursar")).is_err());
+}
+
+#[test]
+fn comparisons_return_policied_bools() {
+    let grade: GPolicied<i64> = GPolicied::make(93, graders_only());
+    let cutoff = open(90);
+    let passed = grade.ge(&cutoff);
+    assert!(passed.export_check_borrow(&ctxt()).is_err());
+    assert!(passed.export_check(&as_role("grader")).unwrap());
+    assert!(!grade.eq(&cutoff).unsafe_export());
+    assert!(grade.ne(&cutoff).unsafe_export());
+    assert!(!grade.lt(&cutoff).unsafe_export());
+    assert_eq!(grade.compare(&cutoff).unsafe_export(), std::cmp::Ordering::Greater);
+    assert!(cutoff.lt(&grade).export_check_borrow(&ctxt()).is_err());
+}
+
+#[test]
+fn branch_results_carry_the_condition_policy() {
+    let passed: GPolicied<bool> = GPolicied::make(true, graders_only());
+    let letter = passed.clone().select("P", "F");
+    assert!(letter.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(letter.export_check(&as_role("grader")).unwrap(), "P");
+
+    let failed = GPolicied::make(false, graders_only()).if_else(|| "P".to_string(), || "F".to_string());
+    assert!(failed.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(failed.unsafe_export(), "F");
+
+    // Branching between policied values keeps both policies after flattening.
+    let fee: GPolicied<i64> = GPolicied::make(10, bursars_only());
+    let charged = passed.select(fee, open(0)).flatten();
+    assert!(charged.export_check_borrow(&as_role("bursar")).is_err());
+    assert_eq!(charged.unsafe_export(), 10);
+}
+
+#[test]
+fn policied_format_merges_argument_policies() {
+    let student_id: GPolicied<String> = GPolicied::make("malte".to_string(), graders_only());
+    let grade = open(93);
+    let line = beaver::policied_format!("{} got {} ({})", student_id, &grade, "final");
+    assert!(line.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(line.export_check(&as_role("grader")).unwrap(), "malte got 93 (final)");
+
+    let plain = beaver::policied_format!("{} of {}", 3, 4);
+    assert_eq!(plain.export_check(&ctxt()).unwrap(), "3 of 4");
+}
+
+#[test]
+fn vec_iteration_keeps_the_policy() {
+    let grades: GPolicied<Vec<i64>> = GPolicied::make(vec![93, 71, 88], graders_only());
+    // GPoliciedIter is not an Iterator, so a filtered result can only be consumed into a
+    // policied value, never stepped through with next() or a for loop.
+    let passing = grades.iter().filter(|g| **g >= 80).count();
+    assert!(passing.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(passing.unsafe_export(), 2);
+    let any_a = grades.iter().filter(|g| **g >= 90).fold(false, |_, _| true);
+    assert!(any_a.export_check_borrow(&ctxt()).is_err());
+    assert!(any_a.export_check(&as_role("grader")).unwrap());
+    let best = grades.iter().max_by(|a, b| a.cmp(b));
+    assert_eq!(best.export_check(&as_role("grader")).unwrap(), Some(&93));
+
+    let curved: GPolicied<Vec<i64>> = grades.into_policied_iter().map(|g| g + 5).collect();
+    assert!(curved.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(curved.export_check(&as_role("grader")).unwrap(), vec![98, 76, 93]);
+}
+
+#[test]
+fn elem_vec_from_vec_keeps_its_policy() {
+    let grades: GPolicied<Vec<i64>> = GPolicied::make(vec![93, 71], graders_only());
+    let elems: PoliciedElemVec<i64> = grades.into();
+    assert!(elems.len().export_check_borrow(&ctxt()).is_err());
+    assert!(elems.get(0).export_check_borrow(&ctxt()).is_err());
+    assert!(elems.filter_exportable(&ctxt()).is_err());
+    assert_eq!(elems.filter_exportable(&as_role("grader")).unwrap(), vec![&93, &71]);
+}
+
+#[test]
+fn elem_vec_lookups_do_not_reveal_the_length() {
+    let mut elems: PoliciedElemVec<i64> = PoliciedElemVec::with_policy(graders_only());
+    elems.push(open(93));
+    elems.push(GPolicied::make(71, bursars_only()));
+
+    // Out of range, the answer still carries the container policy.
+    let missing = elems.get(5);
+    assert!(missing.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(missing.export_check(&as_role("grader")).unwrap(), None);
+    assert_eq!(elems.get(0).export_check(&as_role("grader")).unwrap(), Some(&93));
+    // The element's own policy applies on top.
+    assert!(elems.get(1).export_check_borrow(&as_role("grader")).is_err());
+
+    assert!(elems.pop().export_check_borrow(&as_role("grader")).is_err());
+    assert_eq!(elems.pop().export_check(&as_role("grader")).unwrap(), Some(93));
+    assert!(elems.pop().export_check_borrow(&ctxt()).is_err());
+}
+
+#[test]
+fn elem_map_lookups_do_not_reveal_keys() {
+    let mut grades: PoliciedElemHashMap<String, i64> = PoliciedElemHashMap::with_policy(graders_only());
+    let old = grades.insert("malte".to_string(), open(93));
+    assert!(old.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(old.unsafe_export(), None);
+
+    assert!(grades.get(&"livia".to_string()).export_check_borrow(&ctxt()).is_err());
+    assert_eq!(grades.get(&"malte".to_string()).export_check(&as_role("grader")).unwrap(), Some(&93));
+    assert!(grades.remove(&"livia".to_string()).export_check_borrow(&ctxt()).is_err());
+    assert_eq!(grades.remove(&"malte".to_string()).unsafe_export(), Some(93));
+}
+
+#[test]
+fn option_combinators_keep_the_policy() {
+    let grade: GPolicied<Option<i64>> = GPolicied::make(Some(93), graders_only());
+    assert!(grade.is_some().export_check_borrow(&ctxt()).is_err());
+    let failing = grade.clone().filter(|g| *g < 60);
+    assert!(failing.is_none().unsafe_export());
+    let fallback = failing.unwrap_or(0);
+    assert!(fallback.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(fallback.unsafe_export(), 0);
+
+    // Combining with a second option merges both policies.
+    let fee: GPolicied<Option<i64>> = GPolicied::make(Some(10), bursars_only());
+    let pair = grade.clone().zip(fee);
+    assert!(pair.export_check_borrow(&as_role("grader")).is_err());
+    assert_eq!(pair.unsafe_export(), Some((93, 10)));
+    let either = GPolicied::make(None, Box::new(NonePolicy)).or(grade.clone());
+    assert!(either.export_check_borrow(&ctxt()).is_err());
+
+    let letter = grade.and_then(|g| if g >= 90 { Some("A") } else { None }).ok_or("no grade");
+    assert_eq!(letter.export_check(&as_role("grader")).unwrap(), Ok("A"));
+}
+
+#[test]
+fn result_combinators_keep_the_policy() {
+    let parsed: GPolicied<Result<i64, String>> = GPolicied::make("93".parse::<i64>().map_err(|e| e.to_string()), graders_only());
+    assert!(parsed.is_ok().export_check_borrow(&ctxt()).is_err());
+    let checked = parsed.clone().and_then(|g| if g <= 100 { Ok(g) } else { Err("out of range".to_string()) });
+    assert_eq!(checked.clone().ok().export_check(&as_role("grader")).unwrap(), Some(93));
+    assert!(checked.err().unsafe_export().is_none());
+
+    let bad: GPolicied<Result<i64, String>> = GPolicied::make(Err("not a number".to_string()), graders_only());
+    let len = bad.clone().map_err(|e| e.len());
+    assert_eq!(len.unsafe_export(), Err(12));
+    let zero = bad.unwrap_or_default();
+    assert!(zero.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(zero.unsafe_export(), 0);
+
+    match parsed.get_result() {
+        Ok(g) => assert!(g.export_check_borrow(&ctxt()).is_err()),
+        Err(_) => panic!("expected Ok"),
+    }
+}
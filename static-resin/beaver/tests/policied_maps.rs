@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, HashMap};
+
+use beaver::filter::Context;
+use beaver::generic_policied::{ExternalizePolicy, GPolicied, InternalizePolicy, PoliciedValBTreeMap};
+use beaver::policy::{MergePolicy, NonePolicy, Policied, Policy, PolicyError};
+use serde::{Deserialize, Serialize};
+
+fn ctxt() -> Context {
+    Context::KVContext(HashMap::new())
+}
+
+// Only lets data reach key-value contexts whose "role" is the given one.
+#[derive(Clone, Serialize, Deserialize)]
+struct RolePolicy {
+    role: String,
+}
+
+#[typetag::serde]
+impl Policy for RolePolicy {
+    fn check(&self, ctxt: &Context) -> Result<(), PolicyError> {
+        match ctxt {
+            Context::KVContext(kv) if kv.get("role") == Some(&self.role) => Ok(()),
+            _ => Err(PolicyError { message: format!("Only the {} role may see this", self.role) }),
+        }
+    }
+
+    fn merge(&self, other: &Box<dyn Policy>) -> Result<Box<dyn Policy>, PolicyError> {
+        Ok(Box::new(MergePolicy::make(Box::new(self.clone()), other.clone())))
+    }
+}
+
+fn as_role(role: &str) -> Context {
+    let mut kv = HashMap::new();
+    kv.insert("role".to_string(), role.to_string());
+    Context::KVContext(kv)
+}
+
+fn graders_only() -> Box<dyn Policy> {
+    Box::new(RolePolicy { role: "grader".to_string() })
+}
+
+// The macros are invoked by path, without importing them or the traits they use.
+mod derived {
+    use serde::{Deserialize, Serialize};
+
+    beaver::derive_policied!(Vec<u8>, PoliciedBytes);
+    beaver::derive_policied_map!(PoliciedGradeMap, String, i64, beaver::policy::Policiedi64);
+}
+
+use derived::{PoliciedBytes, PoliciedGradeMap};
+
+#[test]
+fn derive_policied_by_path() {
+    let bytes = PoliciedBytes::make(vec![1, 2], graders_only());
+    assert!(bytes.export_check_borrow(&ctxt()).is_err());
+    let bytes: GPolicied<Vec<u8>> = bytes.into();
+    assert_eq!(bytes.export_check(&as_role("grader")).unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn derived_map_merges_value_policies() {
+    let mut grades = PoliciedGradeMap::new();
+    assert!(grades.insert("malte".to_string(), GPolicied::make(93, Box::new(NonePolicy))).is_none());
+    assert!(grades.export_check_borrow(&ctxt()).is_ok());
+
+    grades.insert("livia".to_string(), GPolicied::make(88, graders_only()));
+    assert!(grades.export_check_borrow(&ctxt()).is_err());
+    // Every value read back carries the merged policy.
+    let malte = grades.get(&"malte".to_string()).unwrap();
+    assert!(malte.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(malte.export_check(&as_role("grader")).unwrap(), 93);
+
+    let old = grades.insert("malte".to_string(), GPolicied::make(95, Box::new(NonePolicy))).unwrap();
+    assert_eq!(old.unsafe_export(), 93);
+    assert_eq!(grades.remove(&"malte".to_string()).unwrap().unsafe_export(), 95);
+    assert!(grades.get(&"malte".to_string()).is_none());
+}
+
+#[test]
+fn btree_map_keeps_one_policy() {
+    let mut grades: PoliciedValBTreeMap<String, i64> = GPolicied::make_default(Default::default());
+    grades.insert("malte".to_string(), GPolicied::make(93, Box::new(NonePolicy)));
+    grades.insert("livia".to_string(), GPolicied::make(88, graders_only()));
+
+    assert!(grades.len().export_check_borrow(&ctxt()).is_err());
+    assert!(grades.get(&"kinan".to_string()).export_check_borrow(&ctxt()).is_err());
+    assert_eq!(grades.get(&"malte".to_string()).export_check(&as_role("grader")).unwrap(), Some(&93));
+
+    // BTreeMap iterates in key order.
+    let names: GPolicied<Vec<&String>> = grades.keys().collect();
+    assert_eq!(names.export_check(&as_role("grader")).unwrap(), vec!["livia", "malte"]);
+    let total: GPolicied<i64> = grades.values().map(|v| *v).sum();
+    assert_eq!(total.unsafe_export(), 181);
+}
+
+#[test]
+fn entry_api_merges_inserted_defaults() {
+    let mut counts: PoliciedValBTreeMap<String, i64> = GPolicied::make_default(Default::default());
+    *counts.entry("malte".to_string()).or_insert(GPolicied::make(0, Box::new(NonePolicy))).unsafe_export() += 1;
+    assert!(counts.export_check_borrow(&ctxt()).is_ok());
+
+    // An occupied entry ignores the default and its policy.
+    counts.entry("malte".to_string()).and_modify(|c| *c += 1).or_insert_with(|| GPolicied::make(0, graders_only()));
+    assert!(counts.export_check_borrow(&ctxt()).is_ok());
+    assert_eq!(counts.get(&"malte".to_string()).unsafe_export(), Some(&2));
+
+    let livia = counts.entry("livia".to_string()).or_insert_with(|| GPolicied::make(7, graders_only()));
+    assert!(livia.export_check_borrow(&ctxt()).is_err());
+    assert!(counts.export_check_borrow(&ctxt()).is_err());
+    assert_eq!(counts.entry("livia".to_string()).key(), "livia");
+}
+
+#[test]
+fn maps_internalize_their_policy_into_the_values() {
+    let mut grades: HashMap<String, GPolicied<i64>> = HashMap::new();
+    grades.insert("malte".to_string(), GPolicied::make(93, graders_only()));
+    grades.insert("livia".to_string(), GPolicied::make(88, Box::new(NonePolicy)));
+    let grades = grades.externalize_policy().internalize_policy();
+    // Each value carries the merged map policy.
+    assert!(grades["livia"].export_check_borrow(&ctxt()).is_err());
+    assert_eq!(*grades["livia"].export_check_borrow(&as_role("grader")).unwrap(), 88);
+
+    let ordered: GPolicied<BTreeMap<String, i64>> = GPolicied::make(
+        vec![("malte".to_string(), 93), ("livia".to_string(), 88)].into_iter().collect(),
+        graders_only(),
+    );
+    let ordered = ordered.internalize_policy();
+    assert_eq!(ordered.keys().collect::<Vec<_>>(), vec!["livia", "malte"]);
+    assert!(ordered["malte"].export_check_borrow(&ctxt()).is_err());
+}
+
+#[test]
+#[allow(deprecated)]
+fn internalize_policy_2_1_forwards() {
+    use beaver::generic_policied::InternalizePolicy_2_1;
+    let grades: GPolicied<HashMap<String, i64>> = GPolicied::make(
+        vec![("malte".to_string(), 93)].into_iter().collect(),
+        graders_only(),
+    );
+    let grades = grades.internalize_policy_2_1();
+    assert!(grades["malte"].export_check_borrow(&ctxt()).is_err());
+}
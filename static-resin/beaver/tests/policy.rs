@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use beaver::filter::{ConsoleContext, Context};
+use beaver::generic_policied::GPolicied;
+use beaver::policy::{NonePolicy, Policied, Policy, PurposePolicy, SharedPolicy};
+
+fn ctxt() -> Context {
+    Context::KVContext(HashMap::new())
+}
+
+fn grading_only() -> Box<dyn Policy> {
+    Box::new(PurposePolicy::make(vec!["grading".to_string()]))
+}
+
+#[test]
+fn purpose_policy_requires_listed_purpose() {
+    let policy = grading_only();
+    assert!(policy.check(&ctxt()).is_err());
+    assert!(policy.check(&ctxt().with_purpose("grading")).is_ok());
+    let err = policy.check(&ctxt().with_purpose("marketing")).unwrap_err();
+    assert_eq!(err.message, "Purpose \"marketing\" is not allowed");
+}
+
+#[test]
+fn purpose_policy_checks_every_hop() {
+    let policy = grading_only();
+    let flow = Context::chain(vec![ctxt(), Context::Console(ConsoleContext::Stdout)]);
+    assert!(policy.check_flow(&flow).is_err());
+    let flow = Context::chain(vec![ctxt(), Context::Console(ConsoleContext::Stdout)]);
+    assert!(policy.check_flow(&flow.with_purpose("grading")).is_ok());
+}
+
+#[test]
+fn merged_purpose_policy_still_applies() {
+    let merged = NonePolicy.merge(&grading_only()).unwrap();
+    assert!(merged.check(&ctxt()).is_err());
+    assert!(merged.check(&ctxt().with_purpose("grading")).is_ok());
+    let billing_only: Box<dyn Policy> = Box::new(PurposePolicy::make(vec!["billing".to_string()]));
+    let merged = grading_only().merge(&billing_only).unwrap();
+    assert!(merged.check(&ctxt().with_purpose("grading")).is_err());
+}
+
+#[test]
+fn shared_policy_delegates_to_the_wrapped_policy() {
+    let shared = SharedPolicy::make(grading_only());
+    assert!(shared.check(&ctxt()).is_err());
+    assert!(shared.check(&ctxt().with_purpose("grading")).is_ok());
+    let flow = Context::chain(vec![ctxt(), Context::Console(ConsoleContext::Stdout)]).with_purpose("grading");
+    assert!(shared.check_flow(&flow).is_ok());
+
+    let merged = shared.merge(&(Box::new(NonePolicy) as Box<dyn Policy>)).unwrap();
+    assert!(merged.check(&ctxt()).is_err());
+
+    let json = serde_json::to_string(&shared).unwrap();
+    let read: Box<dyn Policy> = serde_json::from_str(&json).unwrap();
+    assert!(read.check(&ctxt()).is_err());
+    assert!(read.check(&ctxt().with_purpose("grading")).is_ok());
+}
+
+#[test]
+fn shared_policy_values_cross_threads() {
+    let grade: GPolicied<i64> = GPolicied::make(93, grading_only()).share_policy();
+    let handles: Vec<_> = (0..2).map(|_| {
+        let grade = grade.clone();
+        std::thread::spawn(move || grade.export_check(&ctxt().with_purpose("grading")).unwrap())
+    }).collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), 93);
+    }
+    assert!(grade.export_check_borrow(&ctxt()).is_err());
+}
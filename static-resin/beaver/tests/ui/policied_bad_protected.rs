@@ -0,0 +1,11 @@
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedGrade)]
+pub struct Grade {
+    #[policy_protected = "PoliciedString"]
+    pub student_id: String,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedGrade)]
+pub struct Grade {
+    #[policy_protected]
+    #[policy_protected(beaver::policy::Policiedi64)]
+    pub grade: i64,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+pub struct Grade {
+    #[policy_protected]
+    pub grade: i64,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedSubmission, per_field)]
+pub enum Submission {
+    Text(#[policy_protected] String),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use beaver_derive::Policied;
+
+#[derive(Policied)]
+#[policied(PoliciedBits)]
+pub union Bits {
+    pub int: u32,
+    pub float: f32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use beaver_derive::Policied;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Policied)]
+#[policied(PoliciedGrade, per_feild)]
+pub struct Grade {
+    #[policy_protected]
+    pub grade: i64,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(name = student_id)]
+#[allow_other]
+#[deny_other(message = "Grades stay with the student")]
+pub struct GradePolicy {
+    pub student_id: String,
+}
+
+fn main() {}
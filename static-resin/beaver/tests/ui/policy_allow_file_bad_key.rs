@@ -0,0 +1,10 @@
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(owner = student_id)]
+pub struct GradePolicy {
+    pub student_id: String,
+}
+
+fn main() {}
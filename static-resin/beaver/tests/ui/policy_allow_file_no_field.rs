@@ -0,0 +1,10 @@
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(message = "File must belong to same student")]
+pub struct GradePolicy {
+    pub student_id: String,
+}
+
+fn main() {}
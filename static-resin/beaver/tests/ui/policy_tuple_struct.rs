@@ -0,0 +1,8 @@
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_file(name = 0)]
+pub struct GradePolicy(String);
+
+fn main() {}
@@ -0,0 +1,11 @@
+use beaver_derive::Policy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Policy)]
+#[allow_remote_ip(student_ip, instuctor_ip)]
+pub struct GradePolicy {
+    pub student_ip: String,
+    pub instructor_ip: String,
+}
+
+fn main() {}
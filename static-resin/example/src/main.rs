@@ -3,7 +3,7 @@ extern crate serde_derive;
 
 use std::fs::File;
 mod grade;
-use beaver::{filter, beaverio};
+use beaver::{filter, beaverio, beaver_println};
 use beaver::policy::Policied;
 use std::net;
 
@@ -39,18 +39,13 @@ fn main() {
         TEST EXPORT CHECK
     ************************/
 
-    let f_malte = File::create("malte").expect("Unable to create file");
-    let ctxt_malte = filter::FileContext {
-        file_name: "malte".to_owned(), 
-        path: "src/".to_owned(),
-    };
-
-    let mut bw_malte = beaverio::BeaverBufWriter::safe_create(f_malte, filter::Context::File(ctxt_malte));
+    // The file context (name and canonical directory) is derived from the file itself
+    let mut bw_malte = beaverio::BeaverFile::create("malte").expect("Unable to create file").into_writer();
 
     let mut malte_student_id = Box::new(malte_grade.student_id());
     let kinan_student_id = Box::new(kinan_grade.student_id());
 
-    match bw_malte.safe_write_json(&Box::new(malte_grade.clone())) {
+    match bw_malte.safe_write_json(&malte_grade) {
         Ok(s) => { println!("Wrote Malte's grade successfully with size: {:?}", s); },
         Err(e) => { println!("Uh oh {:?}", e); }
     } 
@@ -69,13 +64,7 @@ fn main() {
         Err(e) => { println!("Successfully errored writing Malte's + Kinan's grade: {:?}", e); }
     } 
 
-    let f_livia = File::create("livia").expect("Unable to create file");
-    let ctxt_livia = filter::FileContext {
-        file_name: "livia".to_owned(), 
-        path: "src/".to_owned(),
-    };
-
-    let mut bw_livia = beaverio::BeaverBufWriter::safe_create(f_livia, filter::Context::File(ctxt_livia));
+    let mut bw_livia = beaverio::BeaverFile::create("livia").expect("Unable to create file").into_writer();
     match bw_livia.safe_write_json(&malte_student_id) {
         Ok(s) => { println!("Wrote Malte + Kinan's grade successfully with size: {:?}", s); },
         Err(e) => { println!("Uh oh {:?}", e); }
@@ -100,13 +89,18 @@ fn main() {
     }
     drop(bw_malte);
 
+    /*********************
+        PRINTING
+    **********************/
+    // Grades may not be shown on the console, so Malte's grade is redacted
+    beaver_println!("Printing Malte's grade: {}", malte_grade.grade());
+    beaver_println!("Printing Sreshtaa's ID after removing the policy: {}", sreshtaa_grade.student_id());
+
     /*************************
         DESERIALIZING DATA
     **************************/    
-    let f_malte =  File::open("malte").unwrap();
-
     // Deserialize grade from Malte's file
-    let mut br_deserialize = beaverio::BeaverBufReader::safe_create(f_malte);
+    let mut br_deserialize = beaverio::BeaverFile::open("malte").unwrap().into_reader();
     let malte_grade_ds: grade::PoliciedGrade = br_deserialize.safe_deserialize_line();
 
     // Try and write malte's grade to a new file, where it will hopefully fail the export_check